@@ -4,6 +4,28 @@ use std::error::Error;
 use std::fs;
 use glsl_to_spirv::ShaderType;
 
+// A shader whose first line is `// permutations: A B C` is additionally compiled once per
+// listed name with `#define <name>` injected after the #version line, to <file>.<name>.spv
+const PERMUTATIONS_PREFIX: &str = "// permutations:";
+
+fn compile(source: &str, shader_type: ShaderType, out_path: &str) -> Result<(), Box<dyn Error>> {
+    use std::io::Read;
+
+    let mut compiled_file = glsl_to_spirv::compile(source, shader_type)?;
+    let mut compiled_bytes = Vec::new();
+    compiled_file.read_to_end(&mut compiled_bytes)?;
+    fs::write(out_path, &compiled_bytes)?;
+
+    Ok(())
+}
+
+fn with_define(source: &str, define: &str) -> String {
+    let mut lines: Vec<String> = source.lines().map(|line| line.to_string()).collect();
+    let version = lines.iter().position(|line| line.trim_start().starts_with("#version")).unwrap_or(0);
+    lines.insert(version + 1, format!("#define {}", define));
+    lines.join("\n")
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     // println!("cargo:rerun-if-changed=src/engine/shaders");
 
@@ -15,7 +37,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         if entry.file_type()?.is_file() {
             let in_path = entry.path();
 
-            let shader_type = in_path.extension().and_then(|ext| {
+            let shader_type = || in_path.extension().and_then(|ext| {
                 match ext.to_string_lossy().as_ref() {
                     "vert" => Some(ShaderType::Vertex),
                     "frag" => Some(ShaderType::Fragment),
@@ -23,19 +45,24 @@ fn main() -> Result<(), Box<dyn Error>> {
                 }
             });
 
-            if let Some(shader_type) = shader_type {
-                use std::io::Read;
-
+            if let Some(stage) = shader_type() {
                 let source = fs::read_to_string(&in_path)?;
-                let mut compiled_file = glsl_to_spirv::compile(&source, shader_type)?;
-                let mut compiled_bytes = Vec::new();
-                compiled_file.read_to_end(&mut compiled_bytes)?;
+                let file_name = in_path.file_name().unwrap().to_string_lossy();
+
+                compile(&source, stage, &format!("compiled_shaders/{}.spv", file_name))?;
 
-                let out_path = format!("compiled_shaders/{}.spv", in_path.file_name().unwrap().to_string_lossy());
-                fs::write(&out_path, &compiled_bytes)?;
+                let permutations = source.lines().next()
+                    .filter(|line| line.starts_with(PERMUTATIONS_PREFIX))
+                    .map(|line| line[PERMUTATIONS_PREFIX.len()..].split_whitespace().map(|name| name.to_string()).collect())
+                    .unwrap_or_else(Vec::new);
+
+                for define in permutations {
+                    let out_path = format!("compiled_shaders/{}.{}.spv", file_name, define);
+                    compile(&with_define(&source, &define), shader_type().unwrap(), &out_path)?;
+                }
             }
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
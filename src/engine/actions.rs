@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+use std::fmt;
+use serde::Deserialize;
+use serde::de::IntoDeserializer;
+use winit::event::{MouseButton, VirtualKeyCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    // held, the camera controllers read these every tick
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    LookUp,
+    LookDown,
+    LookLeft,
+    LookRight,
+    Orbit,
+    Pan,
+    // triggered once per press
+    ResetCamera,
+    ToggleMouseLook,
+    ReleaseMouse,
+    NextController,
+    FocusScene,
+    ViewPerspective,
+    ViewPlan,
+    ViewFrontElevation,
+    ViewSideElevation,
+    ViewIsometric,
+    ViewDimetric,
+    ViewCavalier,
+    ViewCabinet,
+    NextVisualization,
+    ToggleUi,
+    ToggleStatsOverlay,
+    ExportStats,
+    ToggleRenderMode,
+    NextPresentMode,
+    ToggleRecording,
+    ToggleInputRecording,
+    ReplayInputs,
+    CapturePanorama,
+    CapturePoster,
+    CaptureScreenshot,
+    ToggleDebugLines,
+    ToggleGrid,
+    ToggleTaa,
+}
+
+// Every action with the name config files use for it, in the order the UI lists them
+pub const ACTIONS: [(Action, &str); 40] = [
+    (Action::MoveForward, "move_forward"),
+    (Action::MoveBack, "move_back"),
+    (Action::MoveLeft, "move_left"),
+    (Action::MoveRight, "move_right"),
+    (Action::MoveUp, "move_up"),
+    (Action::MoveDown, "move_down"),
+    (Action::LookUp, "look_up"),
+    (Action::LookDown, "look_down"),
+    (Action::LookLeft, "look_left"),
+    (Action::LookRight, "look_right"),
+    (Action::Orbit, "orbit"),
+    (Action::Pan, "pan"),
+    (Action::ResetCamera, "reset_camera"),
+    (Action::ToggleMouseLook, "toggle_mouse_look"),
+    (Action::ReleaseMouse, "release_mouse"),
+    (Action::NextController, "next_controller"),
+    (Action::FocusScene, "focus_scene"),
+    (Action::ViewPerspective, "view_perspective"),
+    (Action::ViewPlan, "view_plan"),
+    (Action::ViewFrontElevation, "view_front_elevation"),
+    (Action::ViewSideElevation, "view_side_elevation"),
+    (Action::ViewIsometric, "view_isometric"),
+    (Action::ViewDimetric, "view_dimetric"),
+    (Action::ViewCavalier, "view_cavalier"),
+    (Action::ViewCabinet, "view_cabinet"),
+    (Action::NextVisualization, "next_visualization"),
+    (Action::ToggleUi, "toggle_ui"),
+    (Action::ToggleStatsOverlay, "toggle_stats_overlay"),
+    (Action::ExportStats, "export_stats"),
+    (Action::ToggleRenderMode, "toggle_render_mode"),
+    (Action::NextPresentMode, "next_present_mode"),
+    (Action::ToggleRecording, "toggle_recording"),
+    (Action::ToggleInputRecording, "toggle_input_recording"),
+    (Action::ReplayInputs, "replay_inputs"),
+    (Action::CapturePanorama, "capture_panorama"),
+    (Action::CapturePoster, "capture_poster"),
+    (Action::CaptureScreenshot, "capture_screenshot"),
+    (Action::ToggleDebugLines, "toggle_debug_lines"),
+    (Action::ToggleGrid, "toggle_grid"),
+    (Action::ToggleTaa, "toggle_taa"),
+];
+
+impl Action {
+    pub fn parse(name: &str) -> Option<Action> {
+        ACTIONS.iter().find(|(_, n)| *n == name).map(|(action, _)| *action)
+    }
+
+    pub fn name(&self) -> &'static str {
+        ACTIONS.iter().find(|(action, _)| action == self).map(|(_, name)| *name).unwrap()
+    }
+
+    // Down while any of its inputs is held, the others trigger when an input is pressed
+    pub fn is_held(&self) -> bool {
+        match self {
+            Action::MoveForward | Action::MoveBack | Action::MoveLeft | Action::MoveRight
+            | Action::MoveUp | Action::MoveDown
+            | Action::LookUp | Action::LookDown | Action::LookLeft | Action::LookRight
+            | Action::Orbit | Action::Pan => true,
+            _ => false,
+        }
+    }
+
+    fn default_inputs(&self) -> Vec<Input> {
+        use VirtualKeyCode::*;
+        let key = |code| vec![Input::Key(code)];
+        match self {
+            Action::MoveForward => key(W),
+            Action::MoveBack => key(S),
+            Action::MoveLeft => key(A),
+            Action::MoveRight => key(D),
+            Action::MoveUp => key(Space),
+            Action::MoveDown => key(LControl),
+            Action::LookUp => key(Up),
+            Action::LookDown => key(Down),
+            Action::LookLeft => key(Left),
+            Action::LookRight => key(Right),
+            Action::Orbit => vec![Input::Mouse(MouseButton::Left)],
+            Action::Pan => vec![Input::Mouse(MouseButton::Middle)],
+            Action::ResetCamera => key(R),
+            Action::ToggleMouseLook => vec![Input::Key(M), Input::Mouse(MouseButton::Right)],
+            Action::ReleaseMouse => key(Escape),
+            Action::NextController => key(C),
+            Action::FocusScene => key(F),
+            Action::ViewPerspective => key(Key0),
+            Action::ViewPlan => key(Key1),
+            Action::ViewFrontElevation => key(Key2),
+            Action::ViewSideElevation => key(Key3),
+            Action::ViewIsometric => key(Key4),
+            Action::ViewDimetric => key(Key5),
+            Action::ViewCavalier => key(Key6),
+            Action::ViewCabinet => key(Key7),
+            Action::NextVisualization => key(V),
+            Action::ToggleUi => key(F1),
+            Action::ToggleStatsOverlay => key(F2),
+            Action::ExportStats => key(F3),
+            Action::ToggleRenderMode => key(F4),
+            Action::NextPresentMode => key(F5),
+            Action::ToggleInputRecording => key(F7),
+            Action::ReplayInputs => key(F8),
+            Action::ToggleRecording => key(F9),
+            Action::CapturePanorama => key(F10),
+            Action::CapturePoster => key(F11),
+            Action::CaptureScreenshot => key(F12),
+            Action::ToggleDebugLines => key(B),
+            Action::ToggleGrid => key(G),
+            Action::ToggleTaa => key(T),
+        }
+    }
+}
+
+// Analog values the camera controllers read, each the sum of its inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    MoveForward,
+    MoveRight,
+    MoveUp,
+    LookRight,
+    LookUp,
+    // read while orbiting or panning
+    DragX,
+    DragY,
+    Zoom,
+}
+
+// Every axis with its name in config files, suffixed so it can't clash with an action
+pub const AXES: [(Axis, &str); 8] = [
+    (Axis::MoveForward, "move_forward_axis"),
+    (Axis::MoveRight, "move_right_axis"),
+    (Axis::MoveUp, "move_up_axis"),
+    (Axis::LookRight, "look_right_axis"),
+    (Axis::LookUp, "look_up_axis"),
+    (Axis::DragX, "drag_x_axis"),
+    (Axis::DragY, "drag_y_axis"),
+    (Axis::Zoom, "zoom_axis"),
+];
+
+impl Axis {
+    pub fn parse(name: &str) -> Option<Axis> {
+        AXES.iter().find(|(_, n)| *n == name).map(|(axis, _)| *axis)
+    }
+
+    fn default_inputs(&self) -> Vec<AxisInput> {
+        let actions = |positive, negative| vec![AxisInput::Actions(positive, negative)];
+        match self {
+            Axis::MoveForward => actions(Action::MoveForward, Action::MoveBack),
+            Axis::MoveRight => actions(Action::MoveRight, Action::MoveLeft),
+            Axis::MoveUp => actions(Action::MoveUp, Action::MoveDown),
+            Axis::LookRight => actions(Action::LookRight, Action::LookLeft),
+            Axis::LookUp => actions(Action::LookUp, Action::LookDown),
+            Axis::DragX => vec![AxisInput::Analog(Analog::MouseX)],
+            Axis::DragY => vec![AxisInput::Analog(Analog::MouseY)],
+            Axis::Zoom => vec![AxisInput::Analog(Analog::Scroll)],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Analog {
+    // pixels since the last tick, right and down are positive
+    MouseX,
+    MouseY,
+    // lines since the last tick, away from the user is positive
+    Scroll,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AxisInput {
+    // 1 while the first action is down, -1 while the second one is
+    Actions(Action, Action),
+    Analog(Analog),
+    // the analog input the other way round
+    Inverted(Analog),
+}
+
+impl AxisInput {
+    // `mouse_x`, `mouse_y` or `scroll`, `-` in front inverts them, or two action names such as
+    // `move_forward/move_back`
+    pub fn parse(name: &str) -> Option<AxisInput> {
+        if let Some(index) = name.find('/') {
+            let positive = Action::parse(&name[..index])?;
+            let negative = Action::parse(&name[index + 1..])?;
+            return Some(AxisInput::Actions(positive, negative));
+        }
+        let analog = |name| match name {
+            "mouse_x" => Some(Analog::MouseX),
+            "mouse_y" => Some(Analog::MouseY),
+            "scroll" => Some(Analog::Scroll),
+            _ => None,
+        };
+        if name.starts_with('-') {
+            analog(&name[1..]).map(AxisInput::Inverted)
+        } else {
+            analog(name).map(AxisInput::Analog)
+        }
+    }
+
+    pub fn is_motion(&self) -> bool {
+        match self {
+            AxisInput::Analog(analog) | AxisInput::Inverted(analog) => *analog != Analog::Scroll,
+            AxisInput::Actions(..) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    // one notch of the scroll wheel, pressed and released at once
+    WheelUp,
+    WheelDown,
+}
+
+impl Input {
+    // Key names as in winit's `VirtualKeyCode`, such as `W`, `Key1`, `F12` or `LControl`,
+    // mouse buttons are `mouse_left`, `mouse_right`, `mouse_middle` or `mouse_<number>`
+    pub fn parse(name: &str) -> Option<Input> {
+        match name {
+            "mouse_left" => Some(Input::Mouse(MouseButton::Left)),
+            "mouse_right" => Some(Input::Mouse(MouseButton::Right)),
+            "mouse_middle" => Some(Input::Mouse(MouseButton::Middle)),
+            "wheel_up" => Some(Input::WheelUp),
+            "wheel_down" => Some(Input::WheelDown),
+            _ if name.starts_with("mouse_") => name["mouse_".len()..].parse().ok().map(|n| Input::Mouse(MouseButton::Other(n))),
+            _ => {
+                let key: Result<VirtualKeyCode, serde::de::value::Error> = VirtualKeyCode::deserialize(name.into_deserializer());
+                key.ok().map(Input::Key)
+            }
+        }
+    }
+
+    // Pressing these triggers actions but doesn't hold them
+    pub fn is_momentary(&self) -> bool {
+        *self == Input::WheelUp || *self == Input::WheelDown
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Input::Key(code) => write!(f, "{:?}", code),
+            Input::Mouse(MouseButton::Left) => write!(f, "mouse_left"),
+            Input::Mouse(MouseButton::Right) => write!(f, "mouse_right"),
+            Input::Mouse(MouseButton::Middle) => write!(f, "mouse_middle"),
+            Input::Mouse(MouseButton::Other(n)) => write!(f, "mouse_{}", n),
+            Input::WheelUp => write!(f, "wheel_up"),
+            Input::WheelDown => write!(f, "wheel_down"),
+        }
+    }
+}
+
+// One input bound to two actions, pressing it would do both
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conflict {
+    pub input: Input,
+    pub bound: Action,
+    pub wanted: Action,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is bound to both {} and {}", self.input, self.bound.name(), self.wanted.name())
+    }
+}
+
+// An action's inputs in a config file, a single one can go without the list
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum BindingConfig {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl BindingConfig {
+    fn names(&self) -> Vec<&str> {
+        match self {
+            BindingConfig::One(name) => vec![name.as_str()],
+            BindingConfig::Many(names) => names.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+// Which inputs trigger which actions and drive which axes. Every input drives at most one
+// action, axes can share theirs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionMap {
+    bindings: HashMap<Action, Vec<Input>>,
+    axes: HashMap<Axis, Vec<AxisInput>>,
+}
+
+impl ActionMap {
+    pub fn new() -> ActionMap {
+        ActionMap {
+            bindings: ACTIONS.iter().map(|(action, _)| (*action, action.default_inputs())).collect(),
+            axes: AXES.iter().map(|(axis, _)| (*axis, axis.default_inputs())).collect(),
+        }
+    }
+
+    // The defaults with the actions and axes named in `config` bound to its inputs instead. Fails
+    // on unknown names and on conflicts, which the other bindings then have to resolve.
+    pub fn from_config(config: &HashMap<String, BindingConfig>) -> Result<ActionMap, String> {
+        let mut map = ActionMap::new();
+        for (name, binding) in config {
+            if let Some(axis) = Axis::parse(name) {
+                let inputs = binding.names().iter()
+                    .map(|input| AxisInput::parse(input).ok_or_else(|| format!("Unknown axis input '{}' for {}", input, name)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                map.axes.insert(axis, inputs);
+                continue;
+            }
+            let action = Action::parse(name).ok_or_else(|| format!("Unknown action '{}'", name))?;
+            let inputs = binding.names().iter()
+                .map(|input| Input::parse(input).ok_or_else(|| format!("Unknown input '{}' for {}", input, name)))
+                .collect::<Result<Vec<_>, _>>()?;
+            map.bindings.insert(action, inputs);
+        }
+
+        let conflicts = map.conflicts();
+        if !conflicts.is_empty() {
+            let conflicts = conflicts.iter().map(Conflict::to_string).collect::<Vec<_>>();
+            return Err(conflicts.join(", "));
+        }
+        Ok(map)
+    }
+
+    pub fn inputs(&self, action: Action) -> &[Input] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn axes(&self) -> &HashMap<Axis, Vec<AxisInput>> {
+        &self.axes
+    }
+
+    // Whether mouse motion drives an axis other than the drags, which are only read while dragging
+    pub fn steers_with_motion(&self) -> bool {
+        self.axes.iter()
+            .filter(|(axis, _)| **axis != Axis::DragX && **axis != Axis::DragY)
+            .any(|(_, inputs)| inputs.iter().any(AxisInput::is_motion))
+    }
+
+    pub fn action(&self, input: &Input) -> Option<Action> {
+        ACTIONS.iter()
+            .map(|(action, _)| *action)
+            .find(|action| self.inputs(*action).contains(input))
+    }
+
+    // Adds an input to an action, unless another action already has it
+    pub fn bind(&mut self, action: Action, input: Input) -> Result<(), Conflict> {
+        match self.action(&input) {
+            Some(bound) if bound == action => Ok(()),
+            Some(bound) => Err(Conflict { input: input, bound: bound, wanted: action }),
+            None => {
+                self.bindings.entry(action).or_insert_with(Vec::new).push(input);
+                Ok(())
+            }
+        }
+    }
+
+    // Takes the input away from whichever action has it
+    pub fn unbind(&mut self, input: &Input) {
+        for inputs in self.bindings.values_mut() {
+            inputs.retain(|bound| bound != input);
+        }
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.bindings.insert(action, Vec::new());
+    }
+
+    // Back to the default inputs, except those another action has taken since
+    pub fn reset(&mut self, action: Action) -> Vec<Conflict> {
+        self.clear(action);
+        action.default_inputs().into_iter()
+            .filter_map(|input| self.bind(action, input).err())
+            .collect()
+    }
+
+    // Inputs bound to more than one action, in the order of ACTIONS
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for (index, (action, _)) in ACTIONS.iter().enumerate() {
+            for input in self.inputs(*action) {
+                let earlier = ACTIONS[..index].iter().find(|(earlier, _)| self.inputs(*earlier).contains(input));
+                if let Some((bound, _)) = earlier {
+                    conflicts.push(Conflict { input: *input, bound: *bound, wanted: *action });
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[(&str, BindingConfig)]) -> HashMap<String, BindingConfig> {
+        entries.iter().map(|(name, binding)| (name.to_string(), binding.clone())).collect()
+    }
+
+    fn one(name: &str) -> BindingConfig {
+        BindingConfig::One(name.to_string())
+    }
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        assert_eq!(ActionMap::new().conflicts(), vec![]);
+    }
+
+    #[test]
+    fn from_config_replaces_the_named_bindings() {
+        let config = config(&[
+            ("move_forward", one("Up")),
+            ("look_up", BindingConfig::Many(vec!["I".to_string(), "mouse_4".to_string()])),
+        ]);
+        let map = ActionMap::from_config(&config).unwrap();
+
+        assert_eq!(map.inputs(Action::MoveForward), &[Input::Key(VirtualKeyCode::Up)]);
+        assert_eq!(map.inputs(Action::LookUp), &[Input::Key(VirtualKeyCode::I), Input::Mouse(MouseButton::Other(4))]);
+        assert_eq!(map.action(&Input::Key(VirtualKeyCode::W)), None);
+        // the rest keep their defaults
+        assert_eq!(map.inputs(Action::MoveBack), &[Input::Key(VirtualKeyCode::S)]);
+    }
+
+    #[test]
+    fn from_config_reports_conflicts() {
+        let config = config(&[("move_forward", one("S"))]);
+        assert_eq!(ActionMap::from_config(&config).unwrap_err(), "S is bound to both move_forward and move_back");
+    }
+
+    #[test]
+    fn from_config_rejects_bad_bindings() {
+        let unknown_action = config(&[("jump", one("Space"))]);
+        assert_eq!(ActionMap::from_config(&unknown_action).unwrap_err(), "Unknown action 'jump'");
+
+        let unknown_input = config(&[("move_forward", one("NotAKey"))]);
+        assert_eq!(ActionMap::from_config(&unknown_input).unwrap_err(), "Unknown input 'NotAKey' for move_forward");
+
+        let unknown_axis_input = config(&[("zoom_axis", one("mouse_z"))]);
+        assert_eq!(ActionMap::from_config(&unknown_axis_input).unwrap_err(), "Unknown axis input 'mouse_z' for zoom_axis");
+    }
+
+    #[test]
+    fn from_config_sets_axes() {
+        let config = config(&[("look_right_axis", BindingConfig::Many(vec!["look_right/look_left".to_string(), "mouse_x".to_string()]))]);
+        let map = ActionMap::from_config(&config).unwrap();
+
+        assert_eq!(map.axes()[&Axis::LookRight], vec![AxisInput::Actions(Action::LookRight, Action::LookLeft), AxisInput::Analog(Analog::MouseX)]);
+        assert!(map.steers_with_motion());
+        assert!(!ActionMap::new().steers_with_motion());
+    }
+
+    #[test]
+    fn parse_axis_inputs() {
+        assert_eq!(AxisInput::parse("mouse_y"), Some(AxisInput::Analog(Analog::MouseY)));
+        assert_eq!(AxisInput::parse("-scroll"), Some(AxisInput::Inverted(Analog::Scroll)));
+        assert_eq!(AxisInput::parse("move_up/move_down"), Some(AxisInput::Actions(Action::MoveUp, Action::MoveDown)));
+        assert_eq!(AxisInput::parse("move_up/jump"), None);
+        assert_eq!(AxisInput::parse("-move_up"), None);
+        assert_eq!(AxisInput::parse("scroll_x"), None);
+    }
+
+    #[test]
+    fn parse_inputs() {
+        assert_eq!(Input::parse("F12"), Some(Input::Key(VirtualKeyCode::F12)));
+        assert_eq!(Input::parse("mouse_right"), Some(Input::Mouse(MouseButton::Right)));
+        assert_eq!(Input::parse("mouse_7"), Some(Input::Mouse(MouseButton::Other(7))));
+        assert_eq!(Input::parse("wheel_down"), Some(Input::WheelDown));
+        assert_eq!(Input::parse("mouse_back"), None);
+        assert_eq!(Input::parse("f12"), None);
+    }
+
+    #[test]
+    fn bind_refuses_inputs_taken_by_another_action() {
+        let mut map = ActionMap::new();
+        let w = Input::Key(VirtualKeyCode::W);
+
+        assert_eq!(map.bind(Action::MoveForward, w), Ok(()));
+        assert_eq!(map.inputs(Action::MoveForward), &[w]);
+        assert_eq!(map.bind(Action::Pan, w), Err(Conflict { input: w, bound: Action::MoveForward, wanted: Action::Pan }));
+
+        map.unbind(&w);
+        assert_eq!(map.bind(Action::Pan, w), Ok(()));
+        assert_eq!(map.action(&w), Some(Action::Pan));
+    }
+
+    #[test]
+    fn reset_skips_inputs_taken_since() {
+        let mut map = ActionMap::new();
+        let m = Input::Key(VirtualKeyCode::M);
+        map.unbind(&m);
+        map.bind(Action::ToggleGrid, m).unwrap();
+
+        let conflicts = map.reset(Action::ToggleMouseLook);
+        assert_eq!(conflicts, vec![Conflict { input: m, bound: Action::ToggleGrid, wanted: Action::ToggleMouseLook }]);
+        assert_eq!(map.inputs(Action::ToggleMouseLook), &[Input::Mouse(MouseButton::Right)]);
+    }
+}
@@ -0,0 +1,82 @@
+use super::types::Vector;
+use super::quaternion::Quaternion;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    // starts slow
+    EaseIn,
+    // ends slow
+    EaseOut,
+    // starts and ends slow
+    EaseInOut,
+}
+
+impl Easing {
+    // Maps linear progress in [0, 1] to eased progress in [0, 1]
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+// Where the camera is and which way it looks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub position: Vector,
+    pub orientation: Quaternion,
+}
+
+impl Pose {
+    // Same angles as `Camera::yaw` and `Camera::pitch`
+    pub fn from_yaw_pitch(position: Vector, yaw: f32, pitch: f32) -> Pose {
+        Pose {
+            position: position,
+            orientation: Quaternion::from_yaw_pitch(yaw, pitch),
+        }
+    }
+
+    // Blends towards `to`, turning the short way round
+    pub fn lerp(&self, to: &Pose, t: f32) -> Pose {
+        Pose {
+            position: self.position + (to.position - self.position) * t,
+            orientation: self.orientation.slerp(&to.orientation, t),
+        }
+    }
+}
+
+// A camera move spread over several simulation ticks
+#[derive(Debug)]
+pub struct CameraAnimation {
+    from: Pose,
+    to: Pose,
+    duration: f32,
+    elapsed: f32,
+    easing: Easing,
+}
+
+impl CameraAnimation {
+    pub fn new(from: Pose, to: Pose, duration: f32, easing: Easing) -> CameraAnimation {
+        CameraAnimation {
+            from: from,
+            to: to,
+            duration: duration,
+            elapsed: 0.0,
+            easing: easing,
+        }
+    }
+
+    // Pose after another `delta_time` seconds and whether the move is over
+    pub fn advance(&mut self, delta_time: f32) -> (Pose, bool) {
+        self.elapsed += delta_time;
+        if self.elapsed >= self.duration {
+            return (self.to, true);
+        }
+        (self.from.lerp(&self.to, self.easing.apply(self.elapsed / self.duration)), false)
+    }
+}
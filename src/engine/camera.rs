@@ -1,12 +1,77 @@
 use glm::{mat4, Matrix4};
-use super::utils::{deg_to_rad, trasform, matrix4_to_array};
-use super::types::{Vector};
+use super::utils::{deg_to_rad, transform, matrix4_to_array, halton};
+use super::types::{Vector, Uniforms, IDENTITY};
+use super::quaternion::Quaternion;
+use super::animation::{CameraAnimation, Easing, Pose};
+use super::targets::DepthMode;
+use super::config::CameraConfig;
 
-use super::input_state::InputState;
 
-const F_NEAR: f32 = 0.1;
-const F_FAR: f32 = 100.0;
-const F_FOV: f32 = 90.0;
+// Looking straight up or down would flip the view over the top
+const MAX_PITCH: f32 = 89.0 * std::f32::consts::PI / 180.0;
+const START_PITCH: f32 = -0.3;
+
+// Parallel projections see this far in front of and behind the camera
+const PARALLEL_DEPTH: f32 = 500.0;
+const VIEW_HEIGHT: f32 = 10.0;
+
+// TAA
+const JITTER_SAMPLES: u32 = 8;
+const TELEPORT_DISTANCE: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    // parallel rays, `Camera::view_height` world units fit the view vertically
+    Orthographic,
+    // orthographic with depth drawn receding at `angle` radians from the x axis,
+    // shortened by `depth_scale`
+    Oblique { angle: f32, depth_scale: f32 },
+}
+
+// Named views for architectural drawings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum View {
+    Perspective,
+    Plan,
+    FrontElevation,
+    SideElevation,
+    Isometric,
+    // 2:1 pixel axonometric, as in pixel art and older games
+    Dimetric,
+    // oblique with depth at full length
+    Cavalier,
+    // oblique with depth at half length, which looks less stretched
+    Cabinet,
+}
+
+impl View {
+    pub fn projection(&self) -> Projection {
+        let angle = 45.0f32.to_radians();
+        match self {
+            View::Perspective => Projection::Perspective,
+            View::Cavalier => Projection::Oblique { angle: angle, depth_scale: 1.0 },
+            View::Cabinet => Projection::Oblique { angle: angle, depth_scale: 0.5 },
+            _ => Projection::Orthographic,
+        }
+    }
+
+    // Orientation the view looks from, perspective keeps whatever the camera has
+    pub fn orientation(&self) -> Option<Quaternion> {
+        let quarter = std::f32::consts::FRAC_PI_2;
+        let (yaw, pitch) = match self {
+            View::Perspective => return None,
+            // north, along -z, is up on the plan
+            View::Plan => (0.0, -quarter),
+            View::FrontElevation | View::Cavalier | View::Cabinet => (0.0, 0.0),
+            View::SideElevation => (-quarter, 0.0),
+            // all three axes equally shortened
+            View::Isometric => (-quarter * 0.5, -(0.5f32.sqrt()).atan()),
+            View::Dimetric => (-quarter * 0.5, -0.5f32.asin()),
+        };
+        Some(Quaternion::from_yaw_pitch(yaw, pitch))
+    }
+}
 
 #[derive(Debug)]
 pub struct Camera {
@@ -14,116 +79,437 @@ pub struct Camera {
     pub far: f32,
     pub fov: f32,
     pub aspect_ratio: f32,
+    pub projection: Projection,
+    // near is depth 1 and the far plane is infinitely far away, which spreads float depth
+    // precision evenly over distance. `far` is then only used to shade depth.
+    pub reverse_z: bool,
+    // world units seen from the bottom to the top of the view by parallel projections
+    pub view_height: f32,
     pub position: Vector,
-    pub rotation: Vector,
+    // never rolled, `yaw` and `pitch` read it as angles
+    pub orientation: Quaternion,
+    // units per second
     pub speed: f32,
+    // radians per second
+    pub rotation_speed: f32,
+    pub jitter_enabled: bool,
+    jitter: (f32, f32),
+    jitter_index: u32,
+    prev_position: Vector,
+    prev_view_projection: [f32; 16],
+    history_invalidated: bool,
+    // state at the start of the latest simulation tick, rendering blends from it
+    tick_position: Vector,
+    tick_orientation: Quaternion,
+    interpolation: f32,
+    animation: Option<CameraAnimation>,
+}
+
+fn start_position() -> Vector {
+    Vector::new(0.0, 1.5, 4.0)
 }
 
 impl Camera {
-    pub fn new(aspect_ratio: f32, near: f32, far: f32, fov: f32) -> Camera {
-        Camera {
+    pub fn new(aspect_ratio: f32, config: &CameraConfig) -> Camera {
+        let mut camera = Camera {
             aspect_ratio: aspect_ratio, 
-            near: near,
-            far: far,
-            fov: fov,
-            position: Vector::new(0.3,-1.4,1.0),
-            rotation: Vector::new(0.0, 0.0, 0.0),
-            speed: 5.0,
-        }
+            near: config.near,
+            far: config.far,
+            fov: config.fov,
+            projection: Projection::Perspective,
+            reverse_z: config.reverse_z,
+            view_height: VIEW_HEIGHT,
+            position: start_position(),
+            orientation: Quaternion::from_yaw_pitch(0.0, START_PITCH),
+            speed: config.speed,
+            rotation_speed: config.rotation_speed,
+            jitter_enabled: true,
+            jitter: (0.0, 0.0),
+            jitter_index: 0,
+            prev_position: start_position(),
+            prev_view_projection: [0.0; 16],
+            history_invalidated: true,
+            tick_position: start_position(),
+            tick_orientation: Quaternion::from_yaw_pitch(0.0, START_PITCH),
+            interpolation: 1.0,
+            animation: None,
+        };
+        camera.prev_view_projection = camera.view_projection();
+        camera
     }
 
-    pub fn update(&mut self, input: &InputState, delta_time: f32) {
-        if input.forward.is_down {
-            self.position.z += self.speed * delta_time;
-        }
-        if input.back.is_down {
-            self.position.z -= self.speed * delta_time;
+    // Remembers where the camera was before a simulation tick moves it, so rendering can blend
+    // from there. Call at the start of every tick, before a controller moves the camera.
+    pub fn begin_tick(&mut self) {
+        self.tick_position = self.position;
+        self.tick_orientation = self.orientation;
+    }
+
+    // Radians turned right from looking down -z
+    pub fn yaw(&self) -> f32 {
+        self.orientation.to_yaw_pitch().0
+    }
+
+    // Radians looked up from the horizon
+    pub fn pitch(&self) -> f32 {
+        self.orientation.to_yaw_pitch().1
+    }
+
+    pub fn set_yaw_pitch(&mut self, yaw: f32, pitch: f32) {
+        self.orientation = Quaternion::from_yaw_pitch(yaw, pitch.max(-MAX_PITCH).min(MAX_PITCH));
+    }
+
+    // Radians around x, then y, then z, as in `Quaternion::from_euler`
+    pub fn rotation(&self) -> Vector {
+        self.orientation.to_euler()
+    }
+
+    // The camera never rolls, so only the heading and pitch of `rotation` are kept
+    pub fn set_rotation(&mut self, rotation: &Vector) {
+        let (yaw, pitch) = Quaternion::from_euler(rotation).to_yaw_pitch();
+        self.set_yaw_pitch(yaw, pitch);
+    }
+
+    // Turns by the given radians within the current tick
+    pub fn turn(&mut self, yaw: f32, pitch: f32) {
+        // standing still keeps views beyond the pitch limit, such as the plan looking straight down
+        if yaw == 0.0 && pitch == 0.0 {
+            return;
         }
-        if input.left.is_down {
-            self.position.x += self.speed * delta_time;
+        let (current_yaw, current_pitch) = self.orientation.to_yaw_pitch();
+        self.set_yaw_pitch(current_yaw + yaw, current_pitch + pitch);
+    }
+
+    pub fn reset(&mut self) {
+        self.position = start_position();
+        self.set_yaw_pitch(0.0, START_PITCH);
+        self.tick_position = self.position;
+        self.tick_orientation = self.orientation;
+        self.invalidate_history();
+    }
+
+    // Direction the camera looks in
+    pub fn forward(&self) -> Vector {
+        self.orientation.rotate(&Vector::new(0.0, 0.0, -1.0))
+    }
+
+    // Forward flattened onto the ground plane
+    pub fn ground_forward(&self) -> Vector {
+        let yaw = self.yaw();
+        Vector::new(yaw.sin(), 0.0, -yaw.cos())
+    }
+
+    pub fn right(&self) -> Vector {
+        self.orientation.rotate(&Vector::new(1.0, 0.0, 0.0))
+    }
+
+    pub fn up(&self) -> Vector {
+        self.orientation.rotate(&Vector::new(0.0, 1.0, 0.0))
+    }
+
+    pub fn depth_mode(&self) -> DepthMode {
+        if self.reverse_z { DepthMode::Reversed } else { DepthMode::Standard }
+    }
+
+    pub fn is_parallel(&self) -> bool {
+        self.projection != Projection::Perspective
+    }
+
+    // Blend factor between the previous and current tick used when rendering
+    pub fn set_interpolation(&mut self, alpha: f32) {
+        self.interpolation = alpha.max(0.0).min(1.0);
+    }
+
+    // True if the latest tick changed the camera
+    pub fn is_moving(&self) -> bool {
+        self.tick_position != self.position || self.tick_orientation != self.orientation
+    }
+
+    fn render_position(&self) -> Vector {
+        lerp(&self.tick_position, &self.position, self.interpolation)
+    }
+
+    fn render_orientation(&self) -> Quaternion {
+        self.tick_orientation.slerp(&self.orientation, self.interpolation)
+    }
+
+    // Anything reprojected from the previous frame is meaningless after a cut
+    pub fn invalidate_history(&mut self) {
+        self.history_invalidated = true;
+    }
+
+    pub fn take_history_invalidated(&mut self) -> bool {
+        let invalidated = self.history_invalidated;
+        self.history_invalidated = false;
+        invalidated
+    }
+
+    // Advances the sub-pixel jitter, call once per rendered frame before uploading uniforms
+    pub fn begin_frame(&mut self, width: u32, height: u32) {
+        if self.render_position().distance(&self.prev_position) > TELEPORT_DISTANCE {
+            self.invalidate_history();
         }
-        if input.right.is_down {
-            self.position.x -= self.speed * delta_time;
+        if self.history_invalidated {
+            self.prev_view_projection = self.view_projection();
         }
-        if input.look_up.is_down {
-            self.rotation.x += self.speed * delta_time;
+
+        if self.jitter_enabled {
+            self.jitter_index = (self.jitter_index + 1) % JITTER_SAMPLES;
+            // offsets in [-0.5, 0.5] pixels, converted to clip space
+            let x = halton(self.jitter_index + 1, 2) - 0.5;
+            let y = halton(self.jitter_index + 1, 3) - 0.5;
+            self.jitter = (x * 2.0 / width.max(1) as f32, y * 2.0 / height.max(1) as f32);
+        } else {
+            self.jitter = (0.0, 0.0);
         }
-        if input.look_down.is_down {
-            self.rotation.x -= self.speed * delta_time;
+    }
+
+    pub fn end_frame(&mut self) {
+        self.prev_view_projection = self.view_projection();
+        self.prev_position = self.render_position();
+    }
+
+    pub fn pose(&self) -> Pose {
+        Pose {
+            position: self.position,
+            orientation: self.orientation,
         }
-        if input.look_left.is_down {
-            self.rotation.y -= self.speed * delta_time;
+    }
+
+    // Jumps to the pose within the current tick
+    pub fn set_pose(&mut self, pose: &Pose) {
+        self.position = pose.position;
+        self.orientation = pose.orientation;
+    }
+
+    // Moves to the pose at once, without blending from the previous tick when rendering or
+    // reprojecting TAA history across the cut
+    pub fn jump_to(&mut self, pose: &Pose) {
+        self.stop_animation();
+        self.set_pose(pose);
+        self.tick_position = self.position;
+        self.tick_orientation = self.orientation;
+        self.invalidate_history();
+    }
+
+    // Leaves the planes as they are and returns false unless 0 < near < far
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) -> bool {
+        if !(near > 0.0 && far > near) {
+            return false;
         }
-        if input.look_right.is_down {
-            self.rotation.y += self.speed * delta_time;
+        self.near = near;
+        self.far = far;
+        true
+    }
+
+    // Puts the camera at `eye` looking at `target`. The camera never rolls, so `up` only picks
+    // the heading when looking straight up or down, where the screen's top points along it.
+    pub fn look_at(&mut self, eye: &Vector, target: &Vector, up: &Vector) {
+        self.stop_animation();
+        self.set_pose(&Camera::look_at_pose(eye, target, up));
+    }
+
+    pub fn look_at_pose(eye: &Vector, target: &Vector, up: &Vector) -> Pose {
+        let direction = (*target - *eye).normalize();
+        let flat = (direction.x * direction.x + direction.z * direction.z).sqrt();
+        let yaw = if flat > 1e-5 {
+            direction.x.atan2(-direction.z)
+        } else if direction.y < 0.0 {
+            // looking down the top of the screen is ahead
+            up.x.atan2(-up.z)
+        } else {
+            // looking up the bottom of the screen is ahead
+            (-up.x).atan2(up.z)
+        };
+        Pose {
+            position: *eye,
+            orientation: Quaternion::from_yaw_pitch(yaw, direction.y.atan2(flat).max(-MAX_PITCH).min(MAX_PITCH)),
         }
-        if input.up.is_down {
-            self.position.y -= self.speed * delta_time;
+    }
+
+    // Glides to the pose over the following ticks, a `duration` of zero jumps there right away
+    pub fn move_to(&mut self, pose: &Pose, duration: f32, easing: Easing) {
+        if duration <= 0.0 {
+            self.animation = None;
+            self.set_pose(pose);
+            return;
         }
-        if input.down.is_down {
-            self.position.y += self.speed * delta_time;
+        self.animation = Some(CameraAnimation::new(self.pose(), *pose, duration, easing));
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.animation.is_some()
+    }
+
+    // Leaves the camera wherever the current move got to
+    pub fn stop_animation(&mut self) {
+        self.animation = None;
+    }
+
+    // Moves the camera along the current animation for one tick
+    pub fn animate(&mut self, delta_time: f32) {
+        let (pose, finished) = match &mut self.animation {
+            Some(animation) => animation.advance(delta_time),
+            None => return,
+        };
+        self.set_pose(&pose);
+        if finished {
+            self.animation = None;
         }
+    }
+
+    // Jittered view-projection used for rasterization
+    pub fn projection(&self) -> [f32; 16] {
+        let jitter = mat4(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            self.jitter.0, self.jitter.1, 0.0, 1.0,
+        );
+        matrix4_to_array(jitter * self.create_projection_matrix())
+    }
 
+    // Unjittered view-projection used for motion vectors
+    pub fn view_projection(&self) -> [f32; 16] {
+        matrix4_to_array(self.create_projection_matrix())
     }
 
-    pub fn reset(&mut self) {
-        self.rotation = Vector::new(0.0, 0.0, 0.0);
-        self.position = Vector::new(0.0, 0.0, 0.0);
+    pub fn still_uniforms(&self) -> Uniforms {
+        self.still_uniforms_with(self.view_projection())
     }
 
-    pub fn moveTo(&mut self, x: f32, y: f32, z: f32) {
+    // Still uniforms for a view-projection derived from this camera, such as a poster tile
+    pub fn still_uniforms_with(&self, view_projection: [f32; 16]) -> Uniforms {
+        let mut uniforms = Uniforms::still(view_projection, self.near, self.far);
+        uniforms.depth = self.depth_uniform();
+        uniforms
+    }
 
+    fn depth_uniform(&self) -> [f32; 4] {
+        let infinite = self.reverse_z && self.projection == Projection::Perspective;
+        [infinite as u32 as f32, 0.0, 0.0, 0.0]
     }
 
-    pub fn rotate(&mut self) {
-        let xrot = deg_to_rad(83.0);
-        let yrot = deg_to_rad(21.0);
+    // Unjittered view-projection looking along `rotation` relative to the camera's heading, level
+    // whatever the pitch. Used for panorama cube faces, which have to share a level horizon.
+    pub fn level_view_projection(&self, rotation: &Matrix4<f32>) -> [f32; 16] {
+        let (yaw, _) = self.render_orientation().to_yaw_pitch();
+        matrix4_to_array(self.create_turned_projection_matrix(&Quaternion::from_yaw_pitch(yaw, 0.0), rotation))
     }
 
-    pub fn projection(&self) -> [f32; 16] {
-        let proj = self.create_projection_matrix();
-        matrix4_to_array(proj)
+    pub fn uniforms(&self) -> Uniforms {
+        Uniforms {
+            transform: self.projection(),
+            view_projection: self.view_projection(),
+            prev_view_projection: self.prev_view_projection,
+            jitter: [self.jitter.0, self.jitter.1, 0.0, 0.0],
+            params: [self.near, self.far, 0.0, 0.0],
+            depth: self.depth_uniform(),
+            model: IDENTITY,
+            prev_model: IDENTITY,
+        }
     }
 
     fn create_projection_matrix(&self) -> Matrix4<f32> {
+        let identity = mat4(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        self.create_turned_projection_matrix(&self.render_orientation(), &identity)
+    }
+
+    // Projections below produce wgpu's 0..1 clip depth, flipped for reverse-Z
+    fn create_perspective_matrix(&self) -> Matrix4<f32> {
         let fov_rad = deg_to_rad(self.fov * 0.5); 
         let fov = 1.0 / fov_rad.tan();
         let x = fov / self.aspect_ratio;
         let y = fov;
+
+        if self.reverse_z {
+            // depth is near / distance, 1 at the near plane and 0 at infinity
+            return mat4(
+                x, 0.0, 0.0, 0.0,
+                0.0, y, 0.0, 0.0,
+                0.0, 0.0, 0.0, -1.0,
+                0.0, 0.0, self.near, 0.0,
+            );
+        }
+
         let z = (self.far + self.near) * (1.0 / (self.near - self.far));
         let w = 2.0 * self.far * self.near * (1.0 / (self.near - self.far));
 
-        let projection = mat4(
+        opengl_fix() * mat4(
             x, 0.0, 0.0, 0.0,
             0.0, y, 0.0, 0.0,
             0.0, 0.0, z, -1.0,
             0.0, 0.0, w, 0.0,
+        )
+    }
+
+    // Parallel projection centred on the camera, which sees behind itself as well so it can
+    // sit in the middle of what it looks at
+    fn create_orthographic_matrix(&self) -> Matrix4<f32> {
+        let height = self.view_height.max(1e-3);
+        let width = height * self.aspect_ratio;
+        let orthographic = opengl_fix() * mat4(
+            2.0 / width, 0.0, 0.0, 0.0,
+            0.0, 2.0 / height, 0.0, 0.0,
+            0.0, 0.0, -1.0 / PARALLEL_DEPTH, 0.0,
+            0.0, 0.0, 0.0, 1.0,
         );
-        // let view = mat4(
-        //     1.0,    0.0,    0.0,    0.0,
-        //     0.0,    1.0,    0.0,    0.0,
-        //     0.0,    0.0,    1.0,    0.0,
-        //     self.position.x, self.position.y, self.position.z, 1.0,
-        // );
-        // let view = mat4(
-        //     1.0,    0.0,    0.0,    self.position.x,
-        //     0.0,    1.0,    0.0,    self.position.y,
-        //     0.0,    0.0,    1.0,    self.position.z,
-        //     0.0,    0.0,    0.0,    1.0,
-        // );
-        // let view = mat4(
-        //     1.0,    0.0,    0.0,    self.position.x,
-        //     0.0,    1.0,    0.0,    self.position.y,
-        //     0.0,    0.0,    1.0,    self.position.z,
-        //     self.position.x, self.position.y, self.position.z, 1.0,
-        // );
-        let opengl_fix = mat4(
-            1.0, 0.0, 0.0, 0.0,
-            0.0, 1.0, 0.0, 0.0,
-            0.0, 0.0, 0.5, 0.0,
-            0.0, 0.0, 0.5, 1.0,
-        );
 
-        projection * opengl_fix * trasform(&self.position, &self.rotation)
+        if self.reverse_z {
+            // depth is linear here, flipping it keeps compares and clears the same as perspective
+            let flip = mat4(
+                1.0, 0.0, 0.0, 0.0,
+                0.0, 1.0, 0.0, 0.0,
+                0.0, 0.0, -1.0, 0.0,
+                0.0, 0.0, 1.0, 1.0,
+            );
+            return flip * orthographic;
+        }
+        orthographic
+    }
+
+    fn create_turned_projection_matrix(&self, orientation: &Quaternion, rotation: &Matrix4<f32>) -> Matrix4<f32> {
+        let projection = match self.projection {
+            Projection::Perspective => self.create_perspective_matrix(),
+            Projection::Orthographic => self.create_orthographic_matrix(),
+            Projection::Oblique { angle, depth_scale } => {
+                // slides points sideways and up in proportion to how far away they are
+                let shear = mat4(
+                    1.0, 0.0, 0.0, 0.0,
+                    0.0, 1.0, 0.0, 0.0,
+                    -depth_scale * angle.cos(), -depth_scale * angle.sin(), 1.0, 0.0,
+                    0.0, 0.0, 0.0, 1.0,
+                );
+                self.create_orthographic_matrix() * shear
+            }
+        };
+        // inverse of the camera's own transform
+        let view = transform(&Vector::new(0.0, 0.0, 0.0), &orientation.conjugate())
+            * transform(&-self.render_position(), &Quaternion::identity());
+
+        projection * *rotation * view
     }
+}
+
+// Maps OpenGL's -1..1 clip depth to wgpu's 0..1, so it goes after the projection
+fn opengl_fix() -> Matrix4<f32> {
+    mat4(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 0.5, 0.0,
+        0.0, 0.0, 0.5, 1.0,
+    )
+}
+
+fn lerp(from: &Vector, to: &Vector, t: f32) -> Vector {
+    Vector::new(
+        from.x + (to.x - from.x) * t,
+        from.y + (to.y - from.y) * t,
+        from.z + (to.z - from.z) * t,
+    )
 }
\ No newline at end of file
@@ -0,0 +1,321 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use futures::executor::block_on;
+
+use super::stats::{FrameStats, Tracked};
+use super::targets::create_texture;
+
+// wgpu requires texture to buffer copies to use rows that are a multiple of this
+const COPY_BYTES_PER_ROW_ALIGNMENT: u32 = 256;
+const BYTES_PER_PIXEL: u32 = 4;
+// Conservative limit for the largest offscreen texture a capture renders to
+pub const MAX_CAPTURE_SIZE: u32 = 8192;
+
+// Offscreen texture with the swapchain format that can be read back
+pub fn create_output_texture(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat) -> wgpu::Texture {
+    create_texture(device, width, height, format, wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_DST | wgpu::TextureUsage::COPY_SRC)
+}
+
+// Copies a texture in one of the BGRA8 colour formats back to the CPU as tightly packed RGBA8
+pub fn read_texture_rgba(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, width: u32, height: u32) -> Vec<u8> {
+    let padded_bytes_per_row = padded_bytes_per_row(width);
+    let size = (padded_bytes_per_row * height) as wgpu::BufferAddress;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: size,
+        usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_texture_to_buffer(
+        wgpu::TextureCopyView {
+            texture: texture,
+            mip_level: 0,
+            array_layer: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::BufferCopyView {
+            buffer: &buffer,
+            offset: 0,
+            bytes_per_row: padded_bytes_per_row,
+            rows_per_image: height,
+        },
+        wgpu::Extent3d {
+            width: width,
+            height: height,
+            depth: 1,
+        },
+    );
+    queue.submit(&[encoder.finish()]);
+
+    let mapping_future = buffer.map_read(0, size);
+    device.poll(wgpu::Maintain::Wait);
+    let mapping = block_on(mapping_future).expect("Failed to map the capture buffer");
+    unpad_bgra(mapping.as_slice(), width, height)
+}
+
+// Row length of a texture copy, rounded up to the alignment
+fn padded_bytes_per_row(width: u32) -> u32 {
+    let unpadded_bytes_per_row = width * BYTES_PER_PIXEL;
+    let padding = (COPY_BYTES_PER_ROW_ALIGNMENT - unpadded_bytes_per_row % COPY_BYTES_PER_ROW_ALIGNMENT) % COPY_BYTES_PER_ROW_ALIGNMENT;
+    unpadded_bytes_per_row + padding
+}
+
+// Drops the row padding of copied BGRA8 data and swaps it to RGBA8
+fn unpad_bgra(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let unpadded_bytes_per_row = (width * BYTES_PER_PIXEL) as usize;
+    let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * height as usize);
+    for row in data.chunks(padded_bytes_per_row(width) as usize).take(height as usize) {
+        for bgra in row[..unpadded_bytes_per_row].chunks(BYTES_PER_PIXEL as usize) {
+            pixels.extend_from_slice(&[bgra[2], bgra[1], bgra[0], bgra[3]]);
+        }
+    }
+    pixels
+}
+
+// The frame is composed here, with every overlay, and then copied to the swapchain, whose
+// textures can't be read back, so screenshots get exactly what was presented
+pub struct PresentedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub texture: Tracked<wgpu::Texture>,
+    pub view: wgpu::TextureView,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: Tracked<wgpu::BindGroup>,
+    pipeline: Tracked<wgpu::RenderPipeline>,
+    sampler: Tracked<wgpu::Sampler>,
+}
+
+impl PresentedFrame {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat) -> PresentedFrame {
+        let vs = include_bytes!("../../compiled_shaders/fullscreen.vert.spv");
+        let vs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+
+        let fs = include_bytes!("../../compiled_shaders/copy.frag.spv");
+        let fs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs[..])).unwrap());
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        multisampled: false,
+                        component_type: wgpu::TextureComponentType::Float,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+            ],
+            label: None,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_group_layout],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            layout: &pipeline_layout,
+            vertex_stage: wgpu::ProgrammableStageDescriptor {
+                module: &vs_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                module: &fs_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: wgpu::CullMode::None,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
+                depth_bias_clamp: 0.0,
+            }),
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            color_states: &[wgpu::ColorStateDescriptor {
+                format: format,
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            depth_stencil_state: None,
+            vertex_state: wgpu::VertexStateDescriptor {
+                index_format: wgpu::IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Undefined,
+        });
+
+        let usage = wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_SRC;
+        let texture = create_texture(device, width, height, format, usage);
+        let view = texture.create_default_view();
+        let bind_group = create_copy_bind_group(device, &bind_group_layout, &view, &sampler);
+
+        PresentedFrame {
+            width: width,
+            height: height,
+            texture: Tracked::new(texture),
+            view: view,
+            bind_group_layout: bind_group_layout,
+            bind_group: Tracked::new(bind_group),
+            pipeline: Tracked::new(pipeline),
+            sampler: Tracked::new(sampler),
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat) {
+        let usage = wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_SRC;
+        let texture = create_texture(device, width, height, format, usage);
+        self.view = texture.create_default_view();
+        self.texture = Tracked::new(texture);
+        self.bind_group = Tracked::new(create_copy_bind_group(device, &self.bind_group_layout, &self.view, &self.sampler));
+        self.width = width;
+        self.height = height;
+    }
+
+    // Copies the composed frame to the swapchain texture
+    pub fn present(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView, stats: &mut FrameStats) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: output,
+                resolve_target: None,
+                load_op: wgpu::LoadOp::Clear,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::BLACK,
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        stats.draw(1);
+    }
+
+    // The last presented frame as RGBA8
+    pub fn read(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<u8> {
+        read_texture_rgba(device, queue, &self.texture, self.width, self.height)
+    }
+}
+
+fn create_copy_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, view: &wgpu::TextureView, sampler: &wgpu::Sampler) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: layout,
+        bindings: &[
+            wgpu::Binding { binding: 0, resource: wgpu::BindingResource::TextureView(view) },
+            wgpu::Binding { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+        ],
+        label: None,
+    })
+}
+
+// Box filters an RGBA8 image down by an integer factor
+pub fn downsample(pixels: &[u8], width: u32, height: u32, factor: u32) -> Vec<u8> {
+    if factor <= 1 {
+        return pixels.to_vec();
+    }
+
+    let out_width = width / factor;
+    let out_height = height / factor;
+    let samples = factor * factor;
+    let mut out = Vec::with_capacity((out_width * out_height * BYTES_PER_PIXEL) as usize);
+
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let mut sum = [0u32; 4];
+            for sy in 0..factor {
+                for sx in 0..factor {
+                    let index = (((y * factor + sy) * width + x * factor + sx) * BYTES_PER_PIXEL) as usize;
+                    for channel in 0..4 {
+                        sum[channel] += pixels[index + channel] as u32;
+                    }
+                }
+            }
+            for channel in sum.iter() {
+                out.push((channel / samples) as u8);
+            }
+        }
+    }
+    out
+}
+
+pub fn write_png<P: AsRef<Path>>(path: P, width: u32, height: u32, pixels: &[u8]) -> io::Result<()> {
+    let mut writer = png_writer(path, width, height)?;
+    writer.write_image_data(pixels)?;
+    Ok(())
+}
+
+// RGBA8 PNG with the header written, for images that are written in parts
+pub fn png_writer<P: AsRef<Path>>(path: P, width: u32, height: u32) -> io::Result<png::Writer<BufWriter<File>>> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    Ok(encoder.write_header()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_padded_to_the_copy_alignment() {
+        assert_eq!(padded_bytes_per_row(1), 256);
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+        assert_eq!(padded_bytes_per_row(128), 512);
+    }
+
+    #[test]
+    fn unpad_drops_padding_and_swaps_to_rgba() {
+        // 3x2 pixels, each row padded to 256 bytes
+        let mut data = vec![0xff; 512];
+        for y in 0..2 {
+            for x in 0..3 {
+                let index = y * 256 + x * 4;
+                data[index..index + 4].copy_from_slice(&[x as u8, y as u8, 10, 20]);
+            }
+        }
+
+        let pixels = unpad_bgra(&data, 3, 2);
+        assert_eq!(pixels, vec![
+            10, 0, 0, 20, 10, 0, 1, 20, 10, 0, 2, 20,
+            10, 1, 0, 20, 10, 1, 1, 20, 10, 1, 2, 20,
+        ]);
+    }
+
+    #[test]
+    fn downsample_averages_blocks() {
+        // 4x2 pixels down to 2x1
+        let pixels = [
+            0, 0, 0, 255, 4, 8, 12, 255, 100, 100, 100, 0, 100, 100, 100, 0,
+            8, 16, 24, 255, 12, 24, 36, 255, 200, 200, 200, 4, 200, 200, 200, 4,
+        ];
+        assert_eq!(downsample(&pixels, 4, 2, 2), vec![6, 12, 18, 255, 150, 150, 150, 2]);
+    }
+
+    #[test]
+    fn downsample_by_one_copies() {
+        let pixels = [1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(downsample(&pixels, 2, 1, 1), pixels.to_vec());
+    }
+}
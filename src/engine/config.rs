@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::Deserialize;
+use winit::dpi::PhysicalSize;
+
+use super::actions::BindingConfig;
+use super::camera::Camera;
+use super::pacing::RenderMode;
+use super::recording::{RecordingOutput, RecordingSettings};
+
+// Read from the working directory when no `--config` is given, if it exists
+pub const DEFAULT_CONFIG_PATH: &str = "engine.toml";
+
+// Short flags for the settings changed most often, anything else is `--section.key value`
+const FLAG_ALIASES: [(&str, &str); 14] = [
+    ("width", "window.width"),
+    ("height", "window.height"),
+    ("fullscreen", "window.fullscreen"),
+    ("fov", "camera.fov"),
+    ("near", "camera.near"),
+    ("far", "camera.far"),
+    ("speed", "camera.speed"),
+    ("backend", "renderer.backend"),
+    ("present-mode", "renderer.present_mode"),
+    ("msaa", "renderer.msaa_samples"),
+    ("fps-cap", "renderer.fps_cap"),
+    ("record-width", "capture.recording_width"),
+    ("record-height", "capture.recording_height"),
+    ("record-fps", "capture.recording_fps"),
+];
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    pub window: WindowConfig,
+    pub camera: CameraConfig,
+    pub renderer: RendererConfig,
+    pub capture: CaptureConfig,
+    // action names to the inputs bound to them, actions left out keep their default inputs
+    pub bindings: HashMap<String, BindingConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    // inner size in physical pixels
+    pub width: u32,
+    pub height: u32,
+    // borderless on the current monitor
+    pub fullscreen: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraConfig {
+    pub near: f32,
+    pub far: f32,
+    // vertical, in degrees
+    pub fov: f32,
+    // units per second
+    pub speed: f32,
+    // radians per second
+    pub rotation_speed: f32,
+    pub reverse_z: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RendererConfig {
+    pub backend: Backend,
+    pub power_preference: PowerPreference,
+    pub present_mode: PresentMode,
+    pub render_mode: RenderMode,
+    // 0 is uncapped
+    pub fps_cap: f32,
+    // 1, 2, 4 or 8
+    pub msaa_samples: u32,
+    pub taa: bool,
+    // of the swapchain and everything drawn to it
+    pub color_format: ColorFormat,
+    // linear RGBA
+    pub clear_color: [f64; 4],
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptureConfig {
+    // 1 saves the frame as shown, more renders the view that many times larger and filters it down
+    pub screenshot_supersample: u32,
+    // size of recorded frames, whatever the window size
+    pub recording_width: u32,
+    pub recording_height: u32,
+    // simulated frames per second of a recording
+    pub recording_fps: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    // whichever of Vulkan, Metal and DX12 the platform has
+    Primary,
+    Vulkan,
+    Metal,
+    Dx12,
+    Dx11,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerPreference {
+    Default,
+    LowPower,
+    HighPerformance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorFormat {
+    // shader output is converted to sRGB when written
+    Bgra8UnormSrgb,
+    // shader output is written as it is
+    Bgra8Unorm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentMode {
+    Fifo,
+    Mailbox,
+    Immediate,
+}
+
+impl Default for WindowConfig {
+    fn default() -> WindowConfig {
+        WindowConfig {
+            width: 1280,
+            height: 720,
+            fullscreen: false,
+        }
+    }
+}
+
+impl Default for CameraConfig {
+    fn default() -> CameraConfig {
+        CameraConfig {
+            near: 0.01,
+            far: 1000.0,
+            fov: 90.0,
+            speed: 5.0,
+            rotation_speed: 2.0,
+            reverse_z: false,
+        }
+    }
+}
+
+impl Default for RendererConfig {
+    fn default() -> RendererConfig {
+        RendererConfig {
+            backend: Backend::Primary,
+            power_preference: PowerPreference::Default,
+            present_mode: PresentMode::Mailbox,
+            render_mode: RenderMode::OnDemand,
+            fps_cap: 0.0,
+            msaa_samples: 1,
+            taa: true,
+            color_format: ColorFormat::Bgra8UnormSrgb,
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+impl Default for CaptureConfig {
+    fn default() -> CaptureConfig {
+        CaptureConfig {
+            screenshot_supersample: 1,
+            recording_width: 1920,
+            recording_height: 1080,
+            recording_fps: 60.0,
+        }
+    }
+}
+
+impl Default for EngineConfig {
+    fn default() -> EngineConfig {
+        EngineConfig {
+            window: WindowConfig::default(),
+            camera: CameraConfig::default(),
+            renderer: RendererConfig::default(),
+            capture: CaptureConfig::default(),
+            bindings: HashMap::new(),
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.width.max(1), self.height.max(1))
+    }
+}
+
+impl CameraConfig {
+    // Sets the fields that differ from `old`, the others keep whatever the UI changed them to
+    pub fn apply_changes(&self, old: &CameraConfig, camera: &mut Camera) {
+        if self.near != old.near || self.far != old.far {
+            let near = if self.near != old.near { self.near } else { camera.near };
+            let far = if self.far != old.far { self.far } else { camera.far };
+            camera.set_clip_planes(near, far);
+        }
+        if self.fov != old.fov {
+            camera.fov = self.fov;
+        }
+        if self.speed != old.speed {
+            camera.speed = self.speed;
+        }
+        if self.rotation_speed != old.rotation_speed {
+            camera.rotation_speed = self.rotation_speed;
+        }
+        if self.reverse_z != old.reverse_z {
+            camera.reverse_z = self.reverse_z;
+        }
+    }
+}
+
+impl RendererConfig {
+    pub fn fps_cap(&self) -> Option<f32> {
+        Some(self.fps_cap).filter(|fps| *fps > 0.0)
+    }
+
+    // Anything but 2, 4 or 8 renders with a single sample
+    pub fn msaa_samples(&self) -> u32 {
+        match self.msaa_samples {
+            2 | 4 | 8 => self.msaa_samples,
+            _ => 1,
+        }
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b, a] = self.clear_color;
+        wgpu::Color { r: r, g: g, b: b, a: a }
+    }
+}
+
+impl CaptureConfig {
+    pub fn recording_settings(&self, output: RecordingOutput) -> RecordingSettings {
+        RecordingSettings {
+            width: self.recording_width,
+            height: self.recording_height,
+            fps: self.recording_fps,
+            output: output,
+        }
+    }
+}
+
+impl Backend {
+    pub fn to_wgpu(&self) -> wgpu::BackendBit {
+        match self {
+            Backend::Primary => wgpu::BackendBit::PRIMARY,
+            Backend::Vulkan => wgpu::BackendBit::VULKAN,
+            Backend::Metal => wgpu::BackendBit::METAL,
+            Backend::Dx12 => wgpu::BackendBit::DX12,
+            Backend::Dx11 => wgpu::BackendBit::DX11,
+        }
+    }
+}
+
+impl PowerPreference {
+    pub fn to_wgpu(&self) -> wgpu::PowerPreference {
+        match self {
+            PowerPreference::Default => wgpu::PowerPreference::Default,
+            PowerPreference::LowPower => wgpu::PowerPreference::LowPower,
+            PowerPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+impl ColorFormat {
+    pub fn to_wgpu(&self) -> wgpu::TextureFormat {
+        match self {
+            ColorFormat::Bgra8UnormSrgb => wgpu::TextureFormat::Bgra8UnormSrgb,
+            ColorFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8Unorm,
+        }
+    }
+}
+
+impl PresentMode {
+    pub fn to_wgpu(&self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+// Where the config comes from: an optional file, with command-line flags on top. The flags are
+// kept so they still win after the file is reloaded.
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    pub path: Option<PathBuf>,
+    // input recording to play back from `--replay <path>`
+    pub replay: Option<PathBuf>,
+    overrides: Vec<(String, toml::Value)>,
+}
+
+impl ConfigSource {
+    // Reads `--config <path>`, `--replay <path>` and `--<key> <value>` or `--<key>=<value>` flags, where the key is
+    // an alias or a dotted path such as `camera.fov`. A flag without a value is `true`.
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> io::Result<ConfigSource> {
+        let mut path = None;
+        let mut replay = None;
+        let mut overrides = Vec::new();
+
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            let flag = match arg.strip_prefix("--") {
+                Some(flag) => flag,
+                None => return Err(invalid_input(format!("Unexpected argument '{}'", arg))),
+            };
+            let (key, value) = match flag.find('=') {
+                Some(index) => (flag[..index].to_string(), Some(flag[index + 1..].to_string())),
+                None => {
+                    let value = match args.peek() {
+                        Some(next) if !next.starts_with("--") => args.next(),
+                        _ => None,
+                    };
+                    (flag.to_string(), value)
+                }
+            };
+
+            if key == "config" {
+                let value = value.ok_or_else(|| invalid_input("--config needs a path".to_string()))?;
+                path = Some(PathBuf::from(value));
+                continue;
+            }
+            if key == "replay" {
+                let value = value.ok_or_else(|| invalid_input("--replay needs a path".to_string()))?;
+                replay = Some(PathBuf::from(value));
+                continue;
+            }
+
+            let key = FLAG_ALIASES.iter()
+                .find(|(alias, _)| *alias == key)
+                .map(|(_, key)| key.to_string())
+                .unwrap_or(key);
+            overrides.push((key, parse_value(value.as_deref().unwrap_or("true"))));
+        }
+
+        if path.is_none() && Path::new(DEFAULT_CONFIG_PATH).exists() {
+            path = Some(PathBuf::from(DEFAULT_CONFIG_PATH));
+        }
+        Ok(ConfigSource { path: path, replay: replay, overrides: overrides })
+    }
+
+    pub fn load(&self) -> io::Result<EngineConfig> {
+        let mut table = match &self.path {
+            Some(path) => {
+                let text = fs::read_to_string(path)?;
+                toml::from_str(&text).map_err(|err| invalid_data(path, err))?
+            }
+            None => toml::value::Table::new(),
+        };
+        for (key, value) in &self.overrides {
+            set_dotted(&mut table, key, value.clone())?;
+        }
+
+        toml::Value::Table(table).try_into()
+            .map_err(|err| invalid_data(self.path.as_deref().unwrap_or(Path::new("command line")), err))
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.path.as_ref()
+            .and_then(|path| fs::metadata(path).ok())
+            .and_then(|metadata| metadata.modified().ok())
+    }
+}
+
+// TOML literals keep their type, so `--fov 70` is a number, anything else is a string
+fn parse_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::value::Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+fn set_dotted(table: &mut toml::value::Table, key: &str, value: toml::Value) -> io::Result<()> {
+    let mut parts = key.split('.').collect::<Vec<_>>();
+    let last = parts.pop().unwrap();
+    let mut table = table;
+    for part in parts {
+        let entry = table.entry(part.to_string()).or_insert_with(|| toml::Value::Table(toml::value::Table::new()));
+        table = match entry {
+            toml::Value::Table(inner) => inner,
+            _ => return Err(invalid_input(format!("'{}' in --{} is not a section", part, key))),
+        };
+    }
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn invalid_data<E: std::fmt::Display>(path: &Path, err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn from_args_reads_flags() {
+        let source = ConfigSource::from_args(args(&[
+            "--config", "other.toml", "--replay=inputs.txt", "--fov", "70", "--camera.reverse_z", "--renderer.backend=vulkan", "--msaa=4",
+        ])).unwrap();
+
+        assert_eq!(source.path, Some(PathBuf::from("other.toml")));
+        assert_eq!(source.replay, Some(PathBuf::from("inputs.txt")));
+        assert_eq!(source.overrides, vec![
+            ("camera.fov".to_string(), toml::Value::Integer(70)),
+            ("camera.reverse_z".to_string(), toml::Value::Boolean(true)),
+            ("renderer.backend".to_string(), toml::Value::String("vulkan".to_string())),
+            ("renderer.msaa_samples".to_string(), toml::Value::Integer(4)),
+        ]);
+    }
+
+    #[test]
+    fn from_args_rejects_bad_arguments() {
+        let unexpected = ConfigSource::from_args(args(&["engine.toml"])).unwrap_err();
+        assert_eq!(unexpected.kind(), io::ErrorKind::InvalidInput);
+
+        let no_path = ConfigSource::from_args(args(&["--config", "--fov", "70"])).unwrap_err();
+        assert_eq!(no_path.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn set_dotted_creates_sections() {
+        let mut table = toml::from_str::<toml::value::Table>("[camera]\nnear = 0.5\n").unwrap();
+        set_dotted(&mut table, "camera.fov", toml::Value::Float(70.0)).unwrap();
+        set_dotted(&mut table, "window.width", toml::Value::Integer(800)).unwrap();
+
+        let expected = toml::from_str::<toml::value::Table>("[camera]\nnear = 0.5\nfov = 70.0\n[window]\nwidth = 800\n").unwrap();
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn set_dotted_rejects_values_as_sections() {
+        let mut table = toml::from_str::<toml::value::Table>("[camera]\nfov = 70.0\n").unwrap();
+        let err = set_dotted(&mut table, "camera.fov.x", toml::Value::Integer(1)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn flags_override_the_file() {
+        let path = std::env::temp_dir().join(format!("config-test-{}.toml", std::process::id()));
+        fs::write(&path, "[camera]\nfov = 60.0\nspeed = 2.0\n").unwrap();
+        let source = ConfigSource::from_args(args(&["--config", path.to_str().unwrap(), "--fov", "75.5", "--width", "800"]));
+        let config = source.and_then(|source| source.load());
+        fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.camera.fov, 75.5);
+        assert_eq!(config.camera.speed, 2.0);
+        assert_eq!(config.window.width, 800);
+        assert_eq!(config.window.height, EngineConfig::default().window.height);
+    }
+}
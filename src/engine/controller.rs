@@ -0,0 +1,156 @@
+use super::camera::Camera;
+use super::actions::{Action, Axis};
+use super::input_state::InputState;
+use super::types::Vector;
+
+// FLY
+// double tapping forward runs this many times faster until it's released
+const SPRINT_MULTIPLIER: f32 = 3.0;
+
+// ORBIT
+// radians per pixel dragged
+const ORBIT_SENSITIVITY: f32 = 0.005;
+// fraction of the distance panned per pixel dragged
+const PAN_SENSITIVITY: f32 = 0.002;
+// fraction of the distance moved per scrolled line
+const DOLLY_STEP: f32 = 0.1;
+const MIN_DISTANCE: f32 = 0.05;
+// how far in front of the camera the target is put until something is focused
+const ORBIT_DISTANCE: f32 = 5.0;
+
+// Moves the camera once per simulation tick, `Camera::begin_tick` has already been called
+pub trait CameraController {
+    fn name(&self) -> &'static str;
+
+    // Takes over the camera wherever another controller left it
+    fn activate(&mut self, _camera: &Camera) {}
+
+    fn update(&mut self, camera: &mut Camera, input: &InputState, delta_time: f32);
+
+    // Where the camera has to be, looking along `forward`, to have the whole box in view
+    fn fit(&mut self, camera: &Camera, forward: &Vector, min: &Vector, max: &Vector) -> Vector;
+
+    // Whether grabbed mouse motion should turn the camera
+    fn mouse_look(&self) -> bool {
+        false
+    }
+}
+
+// Distance at which a sphere of `radius` just fits the narrower side of the view
+fn fit_distance(camera: &Camera, radius: f32) -> f32 {
+    let vertical = (camera.fov.to_radians() * 0.5).tan();
+    let narrowest = vertical.min(vertical * camera.aspect_ratio).atan();
+    (radius / narrowest.sin()).max(MIN_DISTANCE)
+}
+
+// View height at which parallel projections fit the box
+pub fn fit_view_height(camera: &Camera, min: &Vector, max: &Vector) -> f32 {
+    let (_, radius) = bounding_sphere(min, max);
+    2.0 * radius * (1.0 / camera.aspect_ratio).max(1.0)
+}
+
+fn bounding_sphere(min: &Vector, max: &Vector) -> (Vector, f32) {
+    ((*min + *max) * 0.5, min.distance(max) * 0.5)
+}
+
+// Scrolling narrows the view of parallel projections, they look the same from any distance
+fn zoom_view(camera: &mut Camera, input: &InputState) {
+    camera.view_height = (camera.view_height * (1.0 - DOLLY_STEP).powf(input.axis(Axis::Zoom))).max(MIN_DISTANCE);
+}
+
+// First person walkthrough, WASD moves along the ground relative to where the camera looks
+pub struct FlyController;
+
+impl CameraController for FlyController {
+    fn name(&self) -> &'static str {
+        "fly"
+    }
+
+    fn update(&mut self, camera: &mut Camera, input: &InputState, delta_time: f32) {
+        let turn = input.axis(Axis::LookRight);
+        let tilt = input.axis(Axis::LookUp);
+        camera.turn(turn * camera.rotation_speed * delta_time, tilt * camera.rotation_speed * delta_time);
+
+        // walk along the ground whatever the pitch, rise and sink along world up
+        let (forward, right) = (camera.ground_forward(), camera.right());
+        let direction = forward * input.axis(Axis::MoveForward)
+            + right * input.axis(Axis::MoveRight)
+            + Vector::new(0.0, 1.0, 0.0) * input.axis(Axis::MoveUp);
+        let sprint = input.key(Action::MoveForward);
+        let speed = if sprint.is_down && sprint.double_tap { camera.speed * SPRINT_MULTIPLIER } else { camera.speed };
+        // diagonals aren't faster
+        camera.position = camera.position + direction.normalize() * (speed * delta_time);
+
+        if camera.is_parallel() {
+            zoom_view(camera, input);
+        }
+    }
+
+    fn fit(&mut self, camera: &Camera, forward: &Vector, min: &Vector, max: &Vector) -> Vector {
+        let (center, radius) = bounding_sphere(min, max);
+        center - *forward * fit_distance(camera, radius)
+    }
+
+    fn mouse_look(&self) -> bool {
+        true
+    }
+}
+
+// Inspection camera circling a target point. Drag to orbit, middle drag to pan and scroll to
+// move closer, the arrow keys orbit as well.
+pub struct OrbitController {
+    pub target: Vector,
+    pub distance: f32,
+}
+
+impl OrbitController {
+    pub fn new() -> OrbitController {
+        OrbitController {
+            target: Vector::new(0.0, 0.0, 0.0),
+            distance: ORBIT_DISTANCE,
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn name(&self) -> &'static str {
+        "orbit"
+    }
+
+    // Orbits the point the camera looks at from the last distance, so taking over doesn't move the view
+    fn activate(&mut self, camera: &Camera) {
+        self.target = camera.position + camera.forward() * self.distance;
+    }
+
+    fn update(&mut self, camera: &mut Camera, input: &InputState, delta_time: f32) {
+        let (dx, dy) = (input.axis(Axis::DragX), input.axis(Axis::DragY));
+
+        let mut turn = input.axis(Axis::LookRight) * camera.rotation_speed * delta_time;
+        let mut tilt = input.axis(Axis::LookUp) * camera.rotation_speed * delta_time;
+        if input.is_down(Action::Orbit) {
+            // dragging moves the scene with the cursor, so the camera goes the other way
+            turn += dx * ORBIT_SENSITIVITY;
+            tilt -= dy * ORBIT_SENSITIVITY;
+        }
+        camera.turn(turn, tilt);
+
+        if input.is_down(Action::Pan) {
+            let scale = self.distance * PAN_SENSITIVITY;
+            self.target = self.target - camera.right() * (dx * scale) + camera.up() * (dy * scale);
+        }
+
+        if camera.is_parallel() {
+            zoom_view(camera, input);
+        } else {
+            self.distance = (self.distance * (1.0 - DOLLY_STEP).powf(input.axis(Axis::Zoom))).max(MIN_DISTANCE);
+        }
+
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+
+    fn fit(&mut self, camera: &Camera, forward: &Vector, min: &Vector, max: &Vector) -> Vector {
+        let (center, radius) = bounding_sphere(min, max);
+        self.distance = fit_distance(camera, radius);
+        center - *forward * self.distance
+    }
+}
@@ -0,0 +1,305 @@
+use std::f32::consts::PI;
+use glm::vec4;
+use zerocopy::AsBytes;
+
+use super::stats::{FrameStats, Tracked};
+use super::targets::{DEPTH_FORMAT, DepthMode};
+use super::types::{Vertex, Vector};
+use super::utils::array_to_matrix4;
+
+pub type Color = [f32; 3];
+
+pub const RED: Color = [1.0, 0.0, 0.0];
+pub const GREEN: Color = [0.0, 1.0, 0.0];
+pub const BLUE: Color = [0.0, 0.0, 1.0];
+pub const WHITE: Color = [1.0, 1.0, 1.0];
+pub const YELLOW: Color = [1.0, 1.0, 0.0];
+
+const CIRCLE_SEGMENTS: usize = 32;
+
+struct DebugLine {
+    start: [f32; 3],
+    end: [f32; 3],
+    color: Color,
+    remaining: f32,
+    drawn: bool,
+}
+
+// Immediate mode line drawing, shapes queued during update are drawn by the next render.
+// A `duration` of None draws the shape until the next tick, shapes are always drawn at least once.
+pub struct DebugDraw {
+    pub enabled: bool,
+    lines: Vec<DebugLine>,
+}
+
+impl DebugDraw {
+    pub fn new() -> DebugDraw {
+        DebugDraw {
+            enabled: true,
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn line(&mut self, start: &Vector, end: &Vector, color: Color, duration: Option<f32>) {
+        self.push(to_array(start), to_array(end), color, duration);
+    }
+
+    pub fn ray(&mut self, origin: &Vector, direction: &Vector, length: f32, color: Color, duration: Option<f32>) {
+        if direction.length() == 0.0 {
+            return;
+        }
+        let end = *origin + direction.normalize() * length;
+        self.line(origin, &end, color, duration);
+    }
+
+    pub fn aabb(&mut self, min: &Vector, max: &Vector, color: Color, duration: Option<f32>) {
+        let corners = [
+            [min.x, min.y, min.z], [max.x, min.y, min.z], [max.x, max.y, min.z], [min.x, max.y, min.z],
+            [min.x, min.y, max.z], [max.x, min.y, max.z], [max.x, max.y, max.z], [min.x, max.y, max.z],
+        ];
+        self.box_edges(&corners, color, duration);
+    }
+
+    pub fn sphere(&mut self, center: &Vector, radius: f32, color: Color, duration: Option<f32>) {
+        let c = to_array(center);
+        for axis in 0..3 {
+            let mut prev = None;
+            for i in 0..=CIRCLE_SEGMENTS {
+                let angle = (i as f32 / CIRCLE_SEGMENTS as f32) * 2.0 * PI;
+                let (s, co) = (angle.sin() * radius, angle.cos() * radius);
+                let point = match axis {
+                    0 => [c[0], c[1] + co, c[2] + s],
+                    1 => [c[0] + co, c[1], c[2] + s],
+                    _ => [c[0] + co, c[1] + s, c[2]],
+                };
+                if let Some(prev) = prev {
+                    self.push(prev, point, color, duration);
+                }
+                prev = Some(point);
+            }
+        }
+    }
+
+    // Outlines the volume seen through a view-projection matrix, such as `Camera::view_projection`
+    pub fn frustum(&mut self, view_projection: &[f32; 16], color: Color, duration: Option<f32>) {
+        let inverse = glm::inverse(&array_to_matrix4(view_projection));
+        let mut corners = [[0.0; 3]; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let x = if i & 1 == 0 { -1.0 } else { 1.0 };
+            let y = if i & 2 == 0 { -1.0 } else { 1.0 };
+            let z = if i & 4 == 0 { 0.0 } else { 1.0 };
+            let p = inverse * vec4(x, y, z, 1.0);
+            *corner = [p.x / p.w, p.y / p.w, p.z / p.w];
+        }
+        // reorder into the winding `box_edges` expects
+        let ordered = [
+            corners[0], corners[1], corners[3], corners[2],
+            corners[4], corners[5], corners[7], corners[6],
+        ];
+        self.box_edges(&ordered, color, duration);
+    }
+
+    pub fn axes(&mut self, origin: &Vector, size: f32, duration: Option<f32>) {
+        let o = to_array(origin);
+        self.push(o, [o[0] + size, o[1], o[2]], RED, duration);
+        self.push(o, [o[0], o[1] + size, o[2]], GREEN, duration);
+        self.push(o, [o[0], o[1], o[2] + size], BLUE, duration);
+    }
+
+    // Grid on the XZ plane centered on `center`
+    pub fn grid(&mut self, center: &Vector, size: f32, divisions: u32, color: Color, duration: Option<f32>) {
+        let half = size * 0.5;
+        let step = size / divisions.max(1) as f32;
+        for i in 0..=divisions.max(1) {
+            let offset = -half + i as f32 * step;
+            self.push(
+                [center.x + offset, center.y, center.z - half],
+                [center.x + offset, center.y, center.z + half],
+                color, duration,
+            );
+            self.push(
+                [center.x - half, center.y, center.z + offset],
+                [center.x + half, center.y, center.z + offset],
+                color, duration,
+            );
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    // Counts down timed shapes in simulated time and drops expired ones, only once they have
+    // been drawn at least once
+    pub fn update(&mut self, delta_time: f32) {
+        for line in self.lines.iter_mut().filter(|line| line.drawn) {
+            line.remaining -= delta_time;
+        }
+        self.lines.retain(|line| !line.drawn || line.remaining > 0.0);
+    }
+
+    // Collects the vertices for this frame
+    pub fn take_vertices(&mut self) -> Vec<Vertex> {
+        let mut vertices = Vec::with_capacity(self.lines.len() * 2);
+        for line in self.lines.iter_mut() {
+            vertices.push(Vertex::s_new(line.start, line.color));
+            vertices.push(Vertex::s_new(line.end, line.color));
+            line.drawn = true;
+        }
+        vertices
+    }
+
+    fn box_edges(&mut self, corners: &[[f32; 3]; 8], color: Color, duration: Option<f32>) {
+        for i in 0..4 {
+            self.push(corners[i], corners[(i + 1) % 4], color, duration);
+            self.push(corners[i + 4], corners[(i + 1) % 4 + 4], color, duration);
+            self.push(corners[i], corners[i + 4], color, duration);
+        }
+    }
+
+    fn push(&mut self, start: [f32; 3], end: [f32; 3], color: Color, duration: Option<f32>) {
+        self.lines.push(DebugLine {
+            start: start,
+            end: end,
+            color: color,
+            remaining: duration.unwrap_or(0.0),
+            drawn: false,
+        });
+    }
+}
+
+// Draws every queued line in a single LineList draw on top of the resolved frame
+pub struct DebugRenderer {
+    pipeline: Tracked<wgpu::RenderPipeline>,
+}
+
+impl DebugRenderer {
+    pub fn new(device: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout, vertex_buffer: wgpu::VertexBufferDescriptor, format: wgpu::TextureFormat, depth_mode: DepthMode) -> DebugRenderer {
+        let vs = include_bytes!("../../compiled_shaders/debug.vert.spv");
+        let vs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+
+        let fs = include_bytes!("../../compiled_shaders/debug.frag.spv");
+        let fs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs[..])).unwrap());
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[bind_group_layout],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            layout: &pipeline_layout,
+            vertex_stage: wgpu::ProgrammableStageDescriptor {
+                module: &vs_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                module: &fs_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: wgpu::CullMode::None,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
+                depth_bias_clamp: 0.0,
+            }),
+            primitive_topology: wgpu::PrimitiveTopology::LineList,
+            color_states: &[wgpu::ColorStateDescriptor {
+                format: format,
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: depth_mode.compare(wgpu::CompareFunction::LessEqual),
+                stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+                stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+                stencil_read_mask: 0,
+                stencil_write_mask: 0,
+            }),
+            vertex_state: wgpu::VertexStateDescriptor {
+                index_format: wgpu::IndexFormat::Uint16,
+                vertex_buffers: &[vertex_buffer],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        DebugRenderer {
+            pipeline: Tracked::new(pipeline),
+        }
+    }
+
+    pub fn render(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView, depth: &wgpu::TextureView, bind_group: &wgpu::BindGroup, vertices: &[Vertex], stats: &mut FrameStats) {
+        if vertices.is_empty() {
+            return;
+        }
+
+        let vertex_buffer = device.create_buffer_with_data(vertices.as_bytes(), wgpu::BufferUsage::VERTEX);
+        stats.upload(vertices.as_bytes().len());
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: output,
+                resolve_target: None,
+                load_op: wgpu::LoadOp::Load,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::BLACK,
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                attachment: depth,
+                depth_load_op: wgpu::LoadOp::Load,
+                depth_store_op: wgpu::StoreOp::Store,
+                clear_depth: 1.0,
+                stencil_load_op: wgpu::LoadOp::Load,
+                stencil_store_op: wgpu::StoreOp::Store,
+                clear_stencil: 0,
+            }),
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, &vertex_buffer, 0, 0);
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+        // lines, no triangles
+        stats.draw(0);
+    }
+}
+
+fn to_array(v: &Vector) -> [f32; 3] {
+    [v.x, v.y, v.z]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_is_scaled_to_its_length() {
+        let mut debug = DebugDraw::new();
+        debug.ray(&Vector::new(1.0, 0.0, 0.0), &Vector::new(0.0, 3.0, 4.0), 10.0, RED, None);
+        debug.ray(&Vector::new(1.0, 0.0, 0.0), &Vector::new(0.0, 0.0, 0.0), 10.0, RED, None);
+        assert_eq!(debug.lines.len(), 1);
+        assert_eq!(debug.lines[0].end, [1.0, 6.0, 8.0]);
+    }
+
+    #[test]
+    fn lines_last_until_drawn_and_expired() {
+        let mut debug = DebugDraw::new();
+        debug.line(&Vector::new(0.0, 0.0, 0.0), &Vector::new(1.0, 0.0, 0.0), RED, None);
+        debug.line(&Vector::new(0.0, 0.0, 0.0), &Vector::new(0.0, 1.0, 0.0), GREEN, Some(0.5));
+
+        // ticks before the first render don't count
+        debug.update(1.0);
+        assert_eq!(debug.take_vertices().len(), 4);
+        // frames without a tick draw the same lines again
+        assert_eq!(debug.take_vertices().len(), 4);
+
+        debug.update(0.25);
+        assert_eq!(debug.take_vertices().len(), 2);
+        debug.update(0.25);
+        assert_eq!(debug.take_vertices().len(), 0);
+    }
+}
@@ -0,0 +1,184 @@
+use glm::vec4;
+use zerocopy::{AsBytes, FromBytes};
+
+use super::stats::{FrameStats, Tracked};
+use super::targets::{DEPTH_FORMAT, DepthMode};
+use super::types::Vector;
+use super::utils::{array_to_matrix4, matrix4_to_array};
+
+const GRID_SPACING: f32 = 1.0;
+const GRID_FADE_DISTANCE: f32 = 60.0;
+const GRID_MAJOR_EVERY: f32 = 10.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, AsBytes, FromBytes)]
+struct GridUniforms {
+    view_projection: [f32; 16],
+    inverse_view_projection: [f32; 16],
+    eye: [f32; 4],
+    params: [f32; 4],
+}
+
+// Infinite XZ ground grid, ray traced per pixel in a fullscreen overlay pass
+pub struct Grid {
+    pub enabled: bool,
+    pipeline: Tracked<wgpu::RenderPipeline>,
+    uniform_buffer: Tracked<wgpu::Buffer>,
+    bind_group: Tracked<wgpu::BindGroup>,
+    depth_mode: DepthMode,
+}
+
+impl Grid {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, depth_mode: DepthMode) -> Grid {
+        let vs = include_bytes!("../../compiled_shaders/fullscreen.vert.spv");
+        let vs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+
+        let fs = include_bytes!("../../compiled_shaders/grid.frag.spv");
+        let fs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs[..])).unwrap());
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<GridUniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                }
+            ],
+            label: None,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: &uniform_buffer,
+                        range: 0..std::mem::size_of::<GridUniforms>() as wgpu::BufferAddress,
+                    }
+                }
+            ],
+            label: None,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_group_layout],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            layout: &pipeline_layout,
+            vertex_stage: wgpu::ProgrammableStageDescriptor {
+                module: &vs_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                module: &fs_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: wgpu::CullMode::None,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
+                depth_bias_clamp: 0.0,
+            }),
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            color_states: &[wgpu::ColorStateDescriptor {
+                format: format,
+                color_blend: wgpu::BlendDescriptor {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: depth_mode.compare(wgpu::CompareFunction::LessEqual),
+                stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+                stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+                stencil_read_mask: 0,
+                stencil_write_mask: 0,
+            }),
+            vertex_state: wgpu::VertexStateDescriptor {
+                index_format: wgpu::IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        Grid {
+            enabled: true,
+            pipeline: Tracked::new(pipeline),
+            uniform_buffer: Tracked::new(uniform_buffer),
+            bind_group: Tracked::new(bind_group),
+            depth_mode: depth_mode,
+        }
+    }
+
+    pub fn render(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView, depth: &wgpu::TextureView, view_projection: &[f32; 16], stats: &mut FrameStats) {
+        if !self.enabled {
+            return;
+        }
+
+        let inverse = glm::inverse(&array_to_matrix4(view_projection));
+        // the projection centre is the only point with x = y = w = 0 in clip space, this stays
+        // the true eye when the projection is off-centre, such as a poster tile
+        let eye = inverse * vec4(0.0, 0.0, 1.0, 0.0);
+        let eye = if eye.w.abs() > 1e-6 {
+            Vector::new(eye.x / eye.w, eye.y / eye.w, eye.z / eye.w)
+        } else {
+            // parallel projections have their eye at infinity and `eye` is the view direction,
+            // stand in with a point one view height back so the grid spacing follows the zoom
+            let unproject = |y: f32| {
+                let p = inverse * vec4(0.0, y, 0.5, 1.0);
+                Vector::new(p.x / p.w, p.y / p.w, p.z / p.w)
+            };
+            let height = unproject(1.0).distance(&unproject(-1.0));
+            unproject(0.0) - Vector::new(eye.x, eye.y, eye.z).normalize() * height
+        };
+        let uniforms = GridUniforms {
+            view_projection: *view_projection,
+            inverse_view_projection: matrix4_to_array(inverse),
+            eye: [eye.x, eye.y, eye.z, 1.0],
+            params: [GRID_SPACING, GRID_FADE_DISTANCE, GRID_MAJOR_EVERY, (self.depth_mode == DepthMode::Reversed) as u32 as f32],
+        };
+        let temp_buffer = device.create_buffer_with_data(uniforms.as_bytes(), wgpu::BufferUsage::COPY_SRC);
+        encoder.copy_buffer_to_buffer(&temp_buffer, 0, &self.uniform_buffer, 0, std::mem::size_of::<GridUniforms>() as wgpu::BufferAddress);
+        stats.upload(std::mem::size_of::<GridUniforms>());
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: output,
+                resolve_target: None,
+                load_op: wgpu::LoadOp::Load,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::BLACK,
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                attachment: depth,
+                depth_load_op: wgpu::LoadOp::Load,
+                depth_store_op: wgpu::StoreOp::Store,
+                clear_depth: 1.0,
+                stencil_load_op: wgpu::LoadOp::Load,
+                stencil_store_op: wgpu::StoreOp::Store,
+                clear_stencil: 0,
+            }),
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        stats.draw(1);
+    }
+}
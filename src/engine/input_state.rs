@@ -1,33 +1,167 @@
+use std::collections::{HashMap, HashSet};
+
+use super::actions::{Action, ActionMap, Analog, Axis, AxisInput, Input, AXES};
+
+// Longest gap between two presses of a key that counts as a double tap, in seconds
+const DOUBLE_TAP_TIME: f32 = 0.3;
+
+// Input for the simulation, gathered from events between ticks and read by the next tick.
+// Timings are in simulation time, so they don't depend on the frame rate.
 pub struct InputState {
-  pub up: Key,
-  pub down: Key,
-  pub left: Key,
-  pub right: Key,
-  pub forward: Key,
-  pub back: Key,
-  pub look_up: Key,
-  pub look_down: Key,
-  pub look_left: Key,
-  pub look_right: Key,
+  // state of every action that has been pressed at least once
+  keys: HashMap<Action, Key>,
+  // mouse motion in pixels and scrolled lines since the last tick
+  mouse_delta: (f32, f32),
+  scroll: f32,
+  // bound keys and buttons currently down, an action is held while any of its inputs is
+  pub held: HashSet<Input>,
+  // what each axis adds up, copied from the action map
+  axes: HashMap<Axis, Vec<AxisInput>>,
 }
 
 impl InputState {
-  pub fn new() -> InputState {
+  pub fn new(actions: &ActionMap) -> InputState {
     InputState {
-      up: Key {is_down: false},
-      down: Key {is_down: false},
-      left: Key {is_down: false},
-      right: Key {is_down: false},
-      forward: Key {is_down: false},
-      back: Key {is_down: false},
-      look_up: Key {is_down: false},
-      look_down: Key {is_down: false},
-      look_left: Key {is_down: false},
-      look_right: Key {is_down: false},
+      keys: HashMap::new(),
+      mouse_delta: (0.0, 0.0),
+      scroll: 0.0,
+      held: HashSet::new(),
+      axes: actions.axes().clone(),
+    }
+  }
+
+  pub fn set_axes(&mut self, actions: &ActionMap) {
+    self.axes = actions.axes().clone();
+  }
+
+  pub fn key(&self, action: Action) -> Key {
+    self.keys.get(&action).cloned().unwrap_or_else(Key::new)
+  }
+
+  pub fn is_down(&self, action: Action) -> bool {
+    self.key(action).is_down
+  }
+
+  // Went down since the last tick
+  pub fn pressed(&self, action: Action) -> bool {
+    self.key(action).pressed
+  }
+
+  // Came up since the last tick
+  pub fn released(&self, action: Action) -> bool {
+    self.key(action).released
+  }
+
+  pub fn axis(&self, axis: Axis) -> f32 {
+    let inputs = self.axes.get(&axis).map(Vec::as_slice).unwrap_or(&[]);
+    inputs.iter().map(|input| match input {
+      AxisInput::Actions(positive, negative) => (self.is_down(*positive) as i32 - self.is_down(*negative) as i32) as f32,
+      AxisInput::Analog(analog) => self.analog(*analog),
+      AxisInput::Inverted(analog) => -self.analog(*analog),
+    }).sum()
+  }
+
+  fn analog(&self, analog: Analog) -> f32 {
+    match analog {
+      Analog::MouseX => self.mouse_delta.0,
+      Analog::MouseY => self.mouse_delta.1,
+      Analog::Scroll => self.scroll,
+    }
+  }
+
+  // Whether any axis has moved since the last tick
+  pub fn any_axis(&self) -> bool {
+    AXES.iter().any(|(axis, _)| self.axis(*axis) != 0.0)
+  }
+
+  pub fn set_down(&mut self, action: Action, down: bool) {
+    let key = self.keys.entry(action).or_insert_with(Key::new);
+    if down == key.is_down {
+      return;
+    }
+    key.is_down = down;
+    if down {
+      key.pressed = true;
+      key.double_tap = key.since_press <= DOUBLE_TAP_TIME;
+      key.since_press = 0.0;
+      key.held_for = 0.0;
+    } else {
+      key.released = true;
+      key.double_tap = false;
+    }
+  }
+
+  pub fn add_mouse_motion(&mut self, dx: f32, dy: f32) {
+    self.mouse_delta.0 += dx;
+    self.mouse_delta.1 += dy;
+  }
+
+  pub fn add_scroll(&mut self, lines: f32) {
+    self.scroll += lines;
+  }
+
+  // Whether any action that steers while held is down
+  pub fn any_down(&self) -> bool {
+    self.keys.iter().any(|(action, key)| key.is_down && action.is_held())
+  }
+
+  // A press recent enough for the next one to be a double tap, ticks have to keep running
+  // until it isn't, or the gap is measured short
+  pub fn awaits_double_tap(&self) -> bool {
+    self.keys.values().any(|key| key.since_press <= DOUBLE_TAP_TIME)
+  }
+
+  // Transitions and motion are handed to the first tick after they happened, call at the end
+  // of every tick
+  pub fn end_tick(&mut self, delta_time: f32) {
+    for key in self.keys.values_mut() {
+      key.pressed = false;
+      key.released = false;
+      key.since_press += delta_time;
+      if key.is_down {
+        key.held_for += delta_time;
+      }
     }
+    self.mouse_delta = (0.0, 0.0);
+    self.scroll = 0.0;
+  }
+
+  pub fn release_all(&mut self) {
+    self.release_actions();
+    self.held.clear();
+  }
+
+  // Starts the actions over without forgetting which inputs are still down
+  pub fn release_actions(&mut self) {
+    self.keys.clear();
+    self.mouse_delta = (0.0, 0.0);
+    self.scroll = 0.0;
   }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Key {
   pub is_down: bool,
-}
\ No newline at end of file
+  // both can be set when a key is tapped between two ticks
+  pub pressed: bool,
+  pub released: bool,
+  // pressed again soon after the previous press, stays set until released
+  pub double_tap: bool,
+  // seconds held so far, or how long the last hold lasted once released
+  pub held_for: f32,
+  // seconds since the last press
+  since_press: f32,
+}
+
+impl Key {
+  fn new() -> Key {
+    Key {
+      is_down: false,
+      pressed: false,
+      released: false,
+      double_tap: false,
+      held_for: 0.0,
+      since_press: std::f32::INFINITY,
+    }
+  }
+}
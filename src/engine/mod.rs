@@ -1,41 +1,148 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 use futures::executor::block_on;
 use zerocopy::AsBytes;
 use winit::{
-    window::{Window, WindowBuilder},
-    event::{Event, WindowEvent, KeyboardInput, VirtualKeyCode, ElementState},
-    event_loop::{EventLoop},
+    window::{Fullscreen, Window, WindowBuilder},
+    event::{Event, WindowEvent, DeviceEvent, KeyboardInput, VirtualKeyCode, ElementState, MouseScrollDelta},
+    event_loop::{EventLoop, ControlFlow},
     dpi::{PhysicalSize}
 };
 
 mod types;
+pub mod config;
+pub mod quaternion;
 mod utils;
-mod camera;
+pub mod camera;
+pub mod animation;
+pub mod controller;
+pub mod actions;
 mod input_state;
+pub mod mouse_look;
+mod targets;
+mod taa;
+mod grid;
+mod visualization;
+mod ui;
+mod stats;
+mod status;
+pub mod timestep;
+pub mod pacing;
+mod present;
+mod capture;
+pub mod recording;
+pub mod replay;
+mod poster;
+pub mod panorama;
+pub mod debug_draw;
+pub mod text;
 
-const CLEAR_COLOR: wgpu::Color = wgpu::Color::BLACK;
-const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
+pub use types::Vector;
 
-// PROJECTION/CAMERA
-const F_NEAR: f32 = 0.01;
-const F_FAR: f32 = 1000.0;
-const F_FOV: f32 = 90.0;
+// Frames drawn after the last change, long enough for TAA to converge on a still image
+const SETTLE_FRAMES: u32 = 16;
+
+// Posters are rendered in tiles at this many times the window size
+const POSTER_SCALE: u32 = 8;
+
+// Height of panoramas, the width is twice this
+const PANORAMA_HEIGHT: u32 = 2048;
+
+// Seconds the camera takes to glide to a focused object
+const FOCUS_DURATION: f32 = 0.6;
+
+// Pixels scrolled by touchpads that count as one scrolled line
+const PIXELS_PER_LINE: f64 = 20.0;
+
+// Id of the only mesh in the scene, hashed to a colour by the mesh colour visualization
+const SCENE_MESH_ID: u32 = 0;
+// Lights affecting the scene, the light complexity visualization shows none as green
+const LIGHT_COUNT: u32 = 0;
+
+// Holding the mouse-look input this many seconds looks around only until it's let go
+const HOLD_TO_LOOK_TIME: f32 = 0.3;
+
+// How often an otherwise idle loop wakes to run ticks while a double tap is still possible
+const INPUT_TICK_INTERVAL: Duration = Duration::from_millis(50);
+
+// How often the config file is checked for changes
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 pub struct Engine {
     surface: wgpu::Surface,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    bind_group: wgpu::BindGroup,
-    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: stats::Tracked<wgpu::BindGroup>,
+    pipelines: visualization::ScenePipelines,
+    // depth convention the pipelines were built for
+    depth_mode: targets::DepthMode,
+    // of the swapchain and everything drawn to it, picked once like the adapter
+    color_format: wgpu::TextureFormat,
+    visualization_mode: visualization::VisualizationMode,
     swapchain: wgpu::SwapChain,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
+    vertex_buffer: stats::Tracked<wgpu::Buffer>,
+    index_buffer: stats::Tracked<wgpu::Buffer>,
     index_buffer_len: u32,
-    uniform_buffer: wgpu::Buffer,
+    edge_index_buffer: stats::Tracked<wgpu::Buffer>,
+    edge_index_buffer_len: u32,
+    uniform_buffer: stats::Tracked<wgpu::Buffer>,
+    // where the scene mesh is placed, and its matrix in the last rendered frame
+    scene_transform: types::Transform,
+    prev_scene_model: [f32; 16],
     camera: camera::Camera,
+    controllers: Vec<Box<dyn controller::CameraController>>,
+    controller: usize,
+    // corners of the box around the scene
+    bounds: (Vector, Vector),
     input: input_state::InputState,
+    actions: actions::ActionMap,
+    // action waiting for the next key or button press to bind
+    rebinding: Option<actions::Action>,
+    // whether the UI had keyboard focus at the last event
+    ui_had_keyboard: bool,
+    mouse_look: mouse_look::MouseLook,
+    size: PhysicalSize<u32>,
+    targets: targets::RenderTargets,
+    depth_resolve: targets::DepthResolve,
+    presented: capture::PresentedFrame,
+    taa: taa::Taa,
+    grid: grid::Grid,
+    debug_draw: debug_draw::DebugDraw,
+    debug_renderer: debug_draw::DebugRenderer,
+    text: text::TextRenderer,
+    ui: ui::DebugUi,
+    stats: stats::Stats,
+    // messages shown on screen in place of printing them
+    status: status::StatusLog,
+    pacer: pacing::FramePacer,
+    redraw_frames: u32,
+    backend: wgpu::Backend,
+    requested_present_mode: wgpu::PresentMode,
+    present_mode: wgpu::PresentMode,
+    // where recordings go, their size and frame rate come from the config
+    pub recording_output: recording::RecordingOutput,
+    recording: Option<recording::Recording>,
+    config: config::EngineConfig,
+    // file and flags the config is reloaded from, with the file's last modification time
+    config_source: Option<(config::ConfigSource, Option<SystemTime>)>,
+    config_checked: Instant,
+    // input being recorded, or played back in place of the user's
+    input_recording: Option<replay::InputRecording>,
+    replay: Option<replay::Replay>,
+    replay_outcome: Option<replay::Outcome>,
+    last_input_recording: Option<PathBuf>,
 }
 
 impl Engine {
+    fn create_controllers() -> Vec<Box<dyn controller::CameraController>> {
+        vec![
+            Box::new(controller::FlyController),
+            Box::new(controller::OrbitController::new()),
+        ]
+    }
+
     fn create_verticies() -> (Vec<types::Vertex>, Vec<u16>) {
         ([
             // front - RED
@@ -69,7 +176,15 @@ impl Engine {
             types::Vertex::new(-1.0, -1.0, -1.0, 1.0, 1.0, 0.0), // 22
             types::Vertex::new(1.0, -1.0, -1.0, 1.0, 1.0, 0.0),  // 23
             
-        ].to_vec(),
+        ].iter().enumerate().map(|(i, vertex)| {
+            // four vertices per face, in the same order as above
+            let normals = [
+                [0.0, 0.0, 1.0], [0.0, 0.0, -1.0], [1.0, 0.0, 0.0],
+                [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, -1.0, 0.0],
+            ];
+            let uvs = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+            vertex.with_normal_uv(normals[i / 4], uvs[i % 4])
+        }).collect(),
             [
                 0, 1, 2, 2, 3, 0, // top
                 4, 5, 6, 6, 7, 4, // bottom
@@ -81,22 +196,26 @@ impl Engine {
         )
     }
 
-    pub fn get_init(title: &str) -> (Window, EventLoop<()>) {
+    pub fn get_init(title: &str, config: &config::WindowConfig) -> (Window, EventLoop<()>) {
         let event_loop = EventLoop::new();
-        let window_builder = WindowBuilder::new().with_title(title);
+        let fullscreen = if config.fullscreen { Some(Fullscreen::Borderless(event_loop.primary_monitor())) } else { None };
+        let window_builder = WindowBuilder::new()
+            .with_title(title)
+            .with_inner_size(config.size())
+            .with_fullscreen(fullscreen);
         let window = window_builder.build(&event_loop).unwrap();
 
         (window, event_loop)
     }
 
-    async fn get_adapter(surface: &wgpu::Surface) -> wgpu::Adapter {
+    async fn get_adapter(surface: &wgpu::Surface, config: &config::RendererConfig) -> wgpu::Adapter {
         wgpu::Adapter::request(
             &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::Default,
+                power_preference: config.power_preference.to_wgpu(),
                 compatible_surface: Some(surface),
             },
-            wgpu::BackendBit::PRIMARY
-        ).await.unwrap()
+            config.backend.to_wgpu()
+        ).await.expect("No adapter for the configured backend")
     }
 
     async fn get_device_queue(adapter: wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
@@ -108,31 +227,39 @@ impl Engine {
         }).await
     }
 
-    pub fn new(window: &Window) -> Engine {
+    pub fn new(window: &Window, config: config::EngineConfig) -> Engine {
         let size = window.inner_size();
         let surface = wgpu::Surface::create(window);
 
-        let adapter = block_on(Engine::get_adapter(&surface));
-        let (device, queue) = block_on(Engine::get_device_queue(adapter));
+        let adapter = block_on(Engine::get_adapter(&surface, &config.renderer));
+        let backend = adapter.get_info().backend;
+        let (device, mut queue) = block_on(Engine::get_device_queue(adapter));
 
         let (verticies, indicies) = Engine::create_verticies();
         let vertex_buffer = device.create_buffer_with_data(verticies.as_bytes(), wgpu::BufferUsage::VERTEX);
         let index_buffer = device.create_buffer_with_data(indicies.as_bytes(), wgpu::BufferUsage::INDEX);
+        let edge_indicies = visualization::edge_indices(&indicies);
+        let edge_index_buffer = device.create_buffer_with_data(edge_indicies.as_bytes(), wgpu::BufferUsage::INDEX);
 
-        let camera = camera::Camera::new(size.width as f32 / size.height as f32, F_NEAR, F_FAR, F_FOV);
-        let uniform_buffer = device.create_buffer_with_data(&camera.projection().as_bytes(), wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST);
-
-        let vs = include_bytes!("../../compiled_shaders/shader.vert.spv");
-        let vs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+        let bounds = verticies.iter().fold(
+            (verticies[0].position(), verticies[0].position()),
+            |(min, max), vertex| {
+                let p = vertex.position();
+                (
+                    Vector::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                    Vector::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+                )
+            },
+        );
 
-        let fs = include_bytes!("../../compiled_shaders/shader.frag.spv");
-        let fs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs[..])).unwrap());
+        let camera = camera::Camera::new(size.width as f32 / size.height as f32, &config.camera);
+        let uniform_buffer = device.create_buffer_with_data(&camera.uniforms().as_bytes(), wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST);
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             bindings: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStage::VERTEX,
+                    visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
                     ty: wgpu::BindingType::UniformBuffer { dynamic: false },
                 }
             ],
@@ -146,165 +273,247 @@ impl Engine {
                     binding: 0,
                     resource: wgpu::BindingResource::Buffer {
                         buffer: &uniform_buffer,
-                        range: 0..64,
+                        range: 0..types::UNIFORMS_SIZE as wgpu::BufferAddress,
                     }
                 }
             ],
             label: None,
         });
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            bind_group_layouts: &[&bind_group_layout],
-        });
+        let mut status = status::StatusLog::new();
+        let color_format = config.renderer.color_format.to_wgpu();
+        let msaa_samples = config.renderer.msaa_samples();
+        if msaa_samples != config.renderer.msaa_samples {
+            status.push(format!("{} MSAA samples aren't supported, rendering with 1", config.renderer.msaa_samples));
+        }
+        let pipelines = visualization::ScenePipelines::new(
+            &device,
+            &bind_group_layout,
+            Engine::create_vertex_buffer(types::VERTEX_SIZE as wgpu::BufferAddress),
+            color_format,
+            msaa_samples,
+            camera.depth_mode(),
+        );
 
-        let vertex_buffer_descriptors = &[
-            Engine::create_vertex_buffer(types::VERTEX_SIZE as wgpu::BufferAddress)
-        ];
+        let requested_present_mode = config.renderer.present_mode.to_wgpu();
+        let present_mode = present::resolve_present_mode(requested_present_mode, backend);
+        let swapchain_description = create_swapchain_description(size, present_mode, color_format);
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            layout: &pipeline_layout,
-            vertex_stage: wgpu::ProgrammableStageDescriptor {
-                module: &vs_module,
-                entry_point: "main",
-            },
-            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-                module: &fs_module,
-                entry_point: "main",
-            }),
-            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: wgpu::CullMode::Back,
-                depth_bias: 0,
-                depth_bias_slope_scale: 0.0,
-                depth_bias_clamp: 0.0,
-            }),
-            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-            color_states: &[wgpu::ColorStateDescriptor {
-                format: TEXTURE_FORMAT,
-                color_blend: wgpu::BlendDescriptor::REPLACE,
-                alpha_blend: wgpu::BlendDescriptor::REPLACE,
-                write_mask: wgpu::ColorWrite::ALL,
-            }],
-            depth_stencil_state: None,
-            vertex_state: wgpu::VertexStateDescriptor {
-                index_format: wgpu::IndexFormat::Uint16,
-                vertex_buffers: vertex_buffer_descriptors,
-            },
-            sample_count: 1,
-            sample_mask: !0,
-            alpha_to_coverage_enabled: false,
-        });
+        let swapchain = device.create_swap_chain(&surface, &swapchain_description);
 
-        let swapchain_description = create_swapchain_description(size);
+        let targets = targets::RenderTargets::new(&device, size.width, size.height, color_format, msaa_samples);
+        let action_map = actions::ActionMap::from_config(&config.bindings).unwrap_or_else(|err| {
+            status.push(format!("Ignoring the configured bindings: {}", err));
+            actions::ActionMap::new()
+        });
 
-        let swapchain = device.create_swap_chain(&surface, &swapchain_description);
+        let mut taa = taa::Taa::new(&device, &targets);
+        taa.enabled = config.renderer.taa;
+        let grid = grid::Grid::new(&device, color_format, camera.depth_mode());
+        let text = text::TextRenderer::new(&device, color_format);
+        let ui = ui::DebugUi::new(window, &device, &mut queue, color_format);
+        let debug_renderer = debug_draw::DebugRenderer::new(
+            &device,
+            &bind_group_layout,
+            Engine::create_vertex_buffer(types::VERTEX_SIZE as wgpu::BufferAddress),
+            color_format,
+            camera.depth_mode(),
+        );
+        let depth_resolve = targets::DepthResolve::new(&device);
+        let presented = capture::PresentedFrame::new(&device, size.width, size.height, color_format);
 
         Engine {
             surface: surface,
             device: device,
             queue: queue,
-            bind_group: bind_group,
-            pipeline: render_pipeline,
+            depth_mode: camera.depth_mode(),
+            color_format: color_format,
+            bind_group_layout: bind_group_layout,
+            bind_group: stats::Tracked::new(bind_group),
+            pipelines: pipelines,
+            visualization_mode: visualization::VisualizationMode::Shaded,
             swapchain: swapchain,
-            vertex_buffer: vertex_buffer,
-            index_buffer: index_buffer,
+            vertex_buffer: stats::Tracked::new(vertex_buffer),
+            index_buffer: stats::Tracked::new(index_buffer),
             index_buffer_len: indicies.len() as u32,
-            uniform_buffer: uniform_buffer,
+            edge_index_buffer: stats::Tracked::new(edge_index_buffer),
+            edge_index_buffer_len: edge_indicies.len() as u32,
+            uniform_buffer: stats::Tracked::new(uniform_buffer),
+            scene_transform: types::Transform::identity(),
+            prev_scene_model: types::IDENTITY,
             camera: camera,
-            input: input_state::InputState::new(),
+            controllers: Engine::create_controllers(),
+            controller: 0,
+            bounds: bounds,
+            input: input_state::InputState::new(&action_map),
+            actions: action_map,
+            rebinding: None,
+            ui_had_keyboard: false,
+            mouse_look: mouse_look::MouseLook::new(),
+            size: size,
+            targets: targets,
+            depth_resolve: depth_resolve,
+            presented: presented,
+            taa: taa,
+            grid: grid,
+            debug_draw: debug_draw::DebugDraw::new(),
+            debug_renderer: debug_renderer,
+            text: text,
+            ui: ui,
+            stats: stats::Stats::new(),
+            status: status,
+            pacer: pacing::FramePacer::new(config.renderer.render_mode, config.renderer.fps_cap()),
+            redraw_frames: SETTLE_FRAMES,
+            backend: backend,
+            requested_present_mode: requested_present_mode,
+            present_mode: present_mode,
+            recording_output: recording::RecordingOutput::default(),
+            recording: None,
+            config: config,
+            config_source: None,
+            config_checked: Instant::now(),
+            input_recording: None,
+            replay: None,
+            replay_outcome: None,
+            last_input_recording: None,
+        }
+    }
+
+    pub fn config(&self) -> &config::EngineConfig {
+        &self.config
+    }
+
+    // Reloads the config whenever its file changes, see `poll_config`
+    pub fn watch_config(&mut self, source: config::ConfigSource) {
+        if source.path.is_some() {
+            let modified = source.modified();
+            self.config_source = Some((source, modified));
         }
     }
 
-    pub fn get_input_state(&mut self, event: &Event<()>, delta_time: f32) {
+    // Call once per event loop iteration, checks the watched file now and then
+    pub fn poll_config(&mut self, window: &Window) {
+        if self.config_checked.elapsed() < CONFIG_POLL_INTERVAL {
+            return;
+        }
+        self.config_checked = Instant::now();
+
+        let config = match &mut self.config_source {
+            Some((source, last_modified)) => {
+                let modified = source.modified();
+                if modified == *last_modified {
+                    return;
+                }
+                *last_modified = modified;
+                match source.load() {
+                    Ok(config) => config,
+                    Err(err) => {
+                        self.status.push(format!("Failed to reload the config, keeping the previous one: {}", err));
+                        return;
+                    }
+                }
+            }
+            None => return,
+        };
+        self.apply_config(window, config);
+    }
+
+    // Applies whatever changed between the current and the new config. Settings changed in the
+    // UI since are only overwritten if the file changes them too.
+    pub fn apply_config(&mut self, window: &Window, config: config::EngineConfig) {
+        let old = std::mem::replace(&mut self.config, config);
+        let new = &self.config;
+
+        if new.window.size() != old.window.size() {
+            window.set_inner_size(new.window.size());
+        }
+        if new.window.fullscreen != old.window.fullscreen {
+            window.set_fullscreen(if new.window.fullscreen { Some(Fullscreen::Borderless(window.current_monitor())) } else { None });
+        }
+
+        new.camera.apply_changes(&old.camera, &mut self.camera);
+
+        let renderer = new.renderer.clone();
+        if self.config.bindings != old.bindings {
+            match actions::ActionMap::from_config(&self.config.bindings) {
+                Ok(map) => self.set_action_map(map),
+                Err(err) => self.status.push(format!("Keeping the current bindings: {}", err)),
+            }
+        }
+        if renderer.present_mode != old.renderer.present_mode {
+            self.set_present_mode(renderer.present_mode.to_wgpu());
+        }
+        if renderer.render_mode != old.renderer.render_mode {
+            self.set_render_mode(renderer.render_mode);
+        }
+        if renderer.fps_cap != old.renderer.fps_cap {
+            self.set_fps_cap(renderer.fps_cap());
+        }
+        if renderer.taa != old.renderer.taa {
+            self.taa.enabled = renderer.taa;
+        }
+        if renderer.msaa_samples != old.renderer.msaa_samples {
+            if renderer.msaa_samples() != renderer.msaa_samples {
+                self.status.push(format!("{} MSAA samples aren't supported, rendering with 1", renderer.msaa_samples));
+            }
+            self.set_msaa_samples(renderer.msaa_samples());
+        }
+        // the adapter and everything drawn to the swapchain are set up once
+        if renderer.backend != old.renderer.backend || renderer.power_preference != old.renderer.power_preference {
+            self.status.push("The backend and power preference apply after a restart");
+        }
+        if renderer.color_format != old.renderer.color_format {
+            self.status.push("The colour format applies after a restart");
+        }
+
+        self.request_redraw();
+    }
+
+    pub fn get_input_state(&mut self, window: &Window, event: &Event<()>) {
         match event {
+            // a replay moves the camera in place of the mouse
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } if self.replay.is_none() => {
+                self.mouse_look.add_motion(delta.0, delta.1);
+                self.add_mouse_motion(delta.0 as f32, delta.1 as f32);
+                // raw motion isn't a window event, and a grabbed cursor may send nothing else
+                if self.mouse_look.is_turning() {
+                    self.request_redraw();
+                }
+            }
             Event::WindowEvent { event, .. } => {
                 // println!("{:?}", event);
                 match event {
                     WindowEvent::KeyboardInput {
                         input: KeyboardInput {
                             virtual_keycode: Some(virtual_code),
-                            state: ElementState::Pressed,
+                            state,
                             ..
                         },
                         ..
                     } => {
-                        match virtual_code {
-                            VirtualKeyCode::W => {
-                                self.input.forward.is_down = true;
-                            }
-                            VirtualKeyCode::S => {
-                                self.input.back.is_down = true;
-                            }
-                            VirtualKeyCode::A => {
-                                self.input.left.is_down = true;
-                            }
-                            VirtualKeyCode::D => {
-                                self.input.right.is_down = true;
-                            }
-                            VirtualKeyCode::Up => {
-                                self.input.look_up.is_down = true;
-                            }
-                            VirtualKeyCode::Down => {
-                                self.input.look_down.is_down = true;
-                            }
-                            VirtualKeyCode::Left => {
-                                self.input.look_left.is_down = true;
-                            }
-                            VirtualKeyCode::Right => {
-                                self.input.look_right.is_down = true;
-                            }
-                            VirtualKeyCode::Space => {
-                                self.input.up.is_down = true;
-                            }
-                            VirtualKeyCode::LControl => {
-                                self.input.down.is_down = true;
-                            }
-                            VirtualKeyCode::R => {
-                                self.camera.reset();
-                            }
-                            _ => {}
+                        self.handle_input(window, actions::Input::Key(*virtual_code), *state == ElementState::Pressed);
+                    },
+                    WindowEvent::MouseInput { state, button, .. } => {
+                        let pressed = *state == ElementState::Pressed;
+                        // a click on the UI shouldn't start a drag, but a release always ends one
+                        if pressed && self.ui.wants_mouse() {
+                            return;
                         }
+                        self.handle_input(window, actions::Input::Mouse(*button), pressed);
                     },
-                    WindowEvent::KeyboardInput {
-                        input: KeyboardInput {
-                            virtual_keycode: Some(virtual_code),
-                            state: ElementState::Released,
-                            ..
-                        },
-                        ..
-                    } => {
-                        match virtual_code {
-                            VirtualKeyCode::W => {
-                                self.input.forward.is_down = false;
+                    WindowEvent::MouseWheel { delta, .. } if !self.ui.wants_mouse() && self.replay.is_none() => {
+                        match delta {
+                            MouseScrollDelta::LineDelta(_, y) => {
+                                self.add_scroll(*y);
+                                // touchpads scroll in pixels and have no notches to bind
+                                if *y != 0.0 {
+                                    let notch = if *y > 0.0 { actions::Input::WheelUp } else { actions::Input::WheelDown };
+                                    self.handle_input(window, notch, true);
+                                }
                             }
-                            VirtualKeyCode::S => {
-                                self.input.back.is_down = false;
+                            MouseScrollDelta::PixelDelta(position) => {
+                                self.add_scroll((position.y / PIXELS_PER_LINE) as f32);
                             }
-                            VirtualKeyCode::A => {
-                                self.input.left.is_down = false;
-                            }
-                            VirtualKeyCode::D => {
-                                self.input.right.is_down = false;
-                            }
-                            VirtualKeyCode::Up => {
-                                self.input.look_up.is_down = false;
-                            }
-                            VirtualKeyCode::Down => {
-                                self.input.look_down.is_down = false;
-                            }
-                            VirtualKeyCode::Left => {
-                                self.input.look_left.is_down = false;
-                            }
-                            VirtualKeyCode::Right => {
-                                self.input.look_right.is_down = false;
-                            }
-                            VirtualKeyCode::Space => {
-                                self.input.up.is_down = false;
-                            }
-                            VirtualKeyCode::LControl => {
-                                self.input.down.is_down = false;
-                            }
-                            _ => {}
                         }
                     },
                     _ => ()
@@ -314,57 +523,1104 @@ impl Engine {
         }
     }
 
-    pub fn update(&mut self, event: &Event<()>, delta_time: f32) {
-        self.get_input_state(event, delta_time);
-        
-        self.camera.update(&self.input, delta_time);
+    // Hands a key or button to the action bound to it, or binds it while rebinding
+    fn handle_input(&mut self, window: &Window, input: actions::Input, pressed: bool) {
+        if pressed {
+            if let Some(action) = self.rebinding.take() {
+                if input != actions::Input::Key(VirtualKeyCode::Escape) {
+                    match self.bind_input(action, input) {
+                        Ok(()) => self.status.push(format!("Bound {} to {}", input, action.name())),
+                        Err(conflict) => self.status.push(format!("Not bound, {}", conflict)),
+                    }
+                }
+                return;
+            }
+        }
+
+        // held keys repeat their press, only the first one counts
+        if !input.is_momentary() {
+            let changed = if pressed { self.input.held.insert(input) } else { self.input.held.remove(&input) };
+            if !changed {
+                return;
+            }
+        }
 
-        self.submit_uniform_data();
+        let action = match self.actions.action(&input) {
+            Some(action) => action,
+            None => return,
+        };
+        if self.replay.is_some() {
+            // the replay stands in for the user, only the key that stops it gets through
+            if pressed && action == actions::Action::ReplayInputs {
+                self.stop_replay();
+            }
+            return;
+        }
+        if action == actions::Action::ToggleInputRecording || action == actions::Action::ReplayInputs {
+            // outside the simulation, so they fire right away rather than on the next tick
+            if pressed {
+                self.trigger(window, action);
+            }
+            return;
+        }
+        let down = (pressed && input.is_momentary()) || self.is_action_held(action);
+        self.set_action_down(action, down);
+        if input.is_momentary() {
+            self.set_action_down(action, false);
+        }
+    }
+
+    fn is_action_held(&self, action: actions::Action) -> bool {
+        self.actions.inputs(action).iter().any(|input| self.input.held.contains(input))
+    }
+
+    // Lets go of actions whose inputs were unbound, and holds those newly bound to held inputs,
+    // without triggering anything
+    fn refresh_held(&mut self) {
+        if self.replay.is_some() {
+            return;
+        }
+        for (action, _) in actions::ACTIONS.iter() {
+            // a press would trigger, so only actions that steer while held are pressed here
+            let down = self.is_action_held(*action) && (action.is_held() || self.input.is_down(*action));
+            self.set_action_down(*action, down);
+        }
+    }
+
+    // Changes to the input go through these so a recording sees them all
+    fn set_action_down(&mut self, action: actions::Action, down: bool) {
+        // the recording and replay keys stay out of the simulation, a replay never presses them
+        if action == actions::Action::ToggleInputRecording || action == actions::Action::ReplayInputs {
+            return;
+        }
+        if self.input.is_down(action) != down {
+            self.input.set_down(action, down);
+            self.record_input(if down { replay::InputEvent::Down(action) } else { replay::InputEvent::Up(action) });
+        }
+    }
+
+    // Motion only moves the camera while looking around, dragging or bound to an axis that's
+    // always read, so nothing else is recorded
+    fn add_mouse_motion(&mut self, dx: f32, dy: f32) {
+        let dragging = self.input.is_down(actions::Action::Orbit) || self.input.is_down(actions::Action::Pan);
+        if !self.mouse_look.is_grabbed() && !dragging && !self.actions.steers_with_motion() {
+            return;
+        }
+        self.record_input(replay::InputEvent::MouseMotion(dx, dy));
+        self.input.add_mouse_motion(dx, dy);
+    }
+
+    fn add_scroll(&mut self, lines: f32) {
+        self.record_input(replay::InputEvent::Scroll(lines));
+        self.input.add_scroll(lines);
+    }
+
+    // Lets go of every action, a replay keeps its own input
+    fn release_input(&mut self) {
+        if self.replay.is_some() {
+            return;
+        }
+        self.record_input(replay::InputEvent::ReleaseAll);
+        self.input.release_all();
+    }
+
+    fn record_input(&mut self, event: replay::InputEvent) {
+        if let Some(recording) = &mut self.input_recording {
+            recording.record(event);
+        }
+    }
+
+    fn trigger(&mut self, window: &Window, action: actions::Action) {
+        match action {
+            actions::Action::ResetCamera => {
+                self.camera.reset();
+            }
+            // the mouse stays free while a replay turns the camera
+            actions::Action::ToggleMouseLook if self.controllers[self.controller].mouse_look() && self.replay.is_none() => {
+                self.set_mouse_grabbed(window, !self.mouse_look.is_grabbed());
+            }
+            actions::Action::ReleaseMouse => {
+                self.set_mouse_grabbed(window, false);
+            }
+            actions::Action::NextController => {
+                self.next_controller(window);
+            }
+            actions::Action::FocusScene => {
+                let (min, max) = self.bounds;
+                self.focus(&min, &max);
+            }
+            actions::Action::ViewPerspective => {
+                self.set_view(camera::View::Perspective);
+            }
+            actions::Action::ViewPlan => {
+                self.set_view(camera::View::Plan);
+            }
+            actions::Action::ViewFrontElevation => {
+                self.set_view(camera::View::FrontElevation);
+            }
+            actions::Action::ViewSideElevation => {
+                self.set_view(camera::View::SideElevation);
+            }
+            actions::Action::ViewIsometric => {
+                self.set_view(camera::View::Isometric);
+            }
+            actions::Action::ViewDimetric => {
+                self.set_view(camera::View::Dimetric);
+            }
+            actions::Action::ViewCavalier => {
+                self.set_view(camera::View::Cavalier);
+            }
+            actions::Action::ViewCabinet => {
+                self.set_view(camera::View::Cabinet);
+            }
+            actions::Action::NextVisualization => {
+                self.visualization_mode = self.visualization_mode.next();
+            }
+            actions::Action::ToggleUi => {
+                self.ui.visible = !self.ui.visible;
+            }
+            actions::Action::ToggleStatsOverlay => {
+                self.stats.overlay = !self.stats.overlay;
+            }
+            actions::Action::ExportStats => {
+                self.export_stats();
+            }
+            actions::Action::CaptureScreenshot => {
+                let path = format!("screenshot-{}.png", utils::timestamp());
+                match self.capture_screenshot(&path) {
+                    Ok(()) => self.status.push(format!("Saved screenshot to {}", path)),
+                    Err(err) => self.status.push(format!("Failed to save screenshot to {}: {}", path, err)),
+                }
+            }
+            actions::Action::CapturePoster => {
+                let path = format!("poster-{}.png", utils::timestamp());
+                let (width, height) = (self.size.width * POSTER_SCALE, self.size.height * POSTER_SCALE);
+                match self.capture_poster(&path, width, height) {
+                    Ok(()) => self.status.push(format!("Saved {}x{} poster to {}", width, height, path)),
+                    Err(err) => self.status.push(format!("Failed to save poster to {}: {}", path, err)),
+                }
+            }
+            actions::Action::CapturePanorama => {
+                let path = format!("panorama-{}.png", utils::timestamp());
+                match self.capture_panorama(&path, PANORAMA_HEIGHT) {
+                    Ok(()) => self.status.push(format!("Saved panorama to {}", path)),
+                    Err(err) => self.status.push(format!("Failed to save panorama to {}: {}", path, err)),
+                }
+            }
+            actions::Action::ToggleRecording => {
+                self.toggle_recording();
+            }
+            actions::Action::ToggleInputRecording => {
+                self.toggle_input_recording();
+            }
+            actions::Action::ReplayInputs => {
+                match self.last_input_recording.clone() {
+                    Some(path) => {
+                        if let Err(err) = self.replay_inputs(window, &path) {
+                            self.status.push(format!("Failed to replay {}: {}", path.display(), err));
+                        }
+                    }
+                    None => self.status.push("No input recording to replay"),
+                }
+            }
+            actions::Action::NextPresentMode => {
+                self.set_present_mode(present::next_present_mode(self.requested_present_mode));
+            }
+            actions::Action::ToggleRenderMode => {
+                self.set_render_mode(match self.pacer.mode {
+                    pacing::RenderMode::Continuous => pacing::RenderMode::OnDemand,
+                    pacing::RenderMode::OnDemand => pacing::RenderMode::Continuous,
+                });
+            }
+            actions::Action::ToggleDebugLines => {
+                self.debug_draw.enabled = !self.debug_draw.enabled;
+            }
+            actions::Action::ToggleGrid => {
+                self.grid.enabled = !self.grid.enabled;
+            }
+            actions::Action::ToggleTaa => {
+                self.taa.enabled = !self.taa.enabled;
+                self.camera.jitter_enabled = self.taa.enabled;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn action_map(&self) -> &actions::ActionMap {
+        &self.actions
+    }
+
+    pub fn set_action_map(&mut self, actions: actions::ActionMap) {
+        self.actions = actions;
+        self.input.set_axes(&self.actions);
+        self.refresh_held();
+    }
+
+    // Adds an input to an action unless another action has it already
+    pub fn bind_input(&mut self, action: actions::Action, input: actions::Input) -> Result<(), actions::Conflict> {
+        self.actions.bind(action, input)?;
+        self.refresh_held();
+        Ok(())
+    }
+
+    // Binds the next key or button pressed to the action, Escape cancels
+    pub fn rebind(&mut self, action: actions::Action) {
+        self.rebinding = Some(action);
+        self.status.push(format!("Press a key or button for {}", action.name()));
+    }
+
+    pub fn handle_ui_event(&mut self, window: &Window, event: &Event<()>) {
+        self.ui.handle_event(window, event);
+    }
+
+    pub fn handle_event(&mut self, window: &Window, event: &Event<()>) {
+        if let Event::WindowEvent { .. } = event {
+            // input, focus and UI hover can all change what's on screen
+            self.request_redraw();
+        }
+
+        if let Event::WindowEvent { event: WindowEvent::Focused(false), .. } = event {
+            // keys released in another window never arrive here
+            self.set_mouse_grabbed(window, false);
+            self.release_input();
+        }
+
+        let ui_keyboard = self.ui.wants_keyboard();
+        if ui_keyboard && !self.ui_had_keyboard {
+            // typing into a field shouldn't move the camera, what was held when it got focus lets go
+            self.release_input();
+        }
+        self.ui_had_keyboard = ui_keyboard;
+        if !ui_keyboard {
+            self.get_input_state(window, event);
+        }
+    }
+
+    // Advances the simulation by one fixed step
+    pub fn update(&mut self, window: &Window, delta_time: f32) {
+        let start = Instant::now();
+
+        // replayed input goes in before the tick, the same as it went in when recorded
+        let replaying = self.replay.is_some();
+        let replayed_turn = self.apply_replay_events();
+        let turning = if replaying { replayed_turn.is_some() } else { self.mouse_look.is_turning() };
+        let mut turn = (0.0, 0.0);
+
+        self.camera.begin_tick();
+        // fires once per press, however many of its inputs are held or how often it's tapped
+        // between two ticks
+        for (action, _) in actions::ACTIONS.iter() {
+            if !action.is_held() && self.input.pressed(*action) {
+                self.trigger(window, *action);
+            }
+        }
+        // holding the mouse-look input looks around until it's let go, a tap toggles it
+        let look = actions::Action::ToggleMouseLook;
+        if self.input.released(look) && self.input.key(look).held_for >= HOLD_TO_LOOK_TIME {
+            self.set_mouse_grabbed(window, false);
+        }
+
+        if self.camera.is_animating() && self.has_user_input(turning) {
+            self.camera.stop_animation();
+            self.controllers[self.controller].activate(&self.camera);
+        }
+        if self.camera.is_animating() {
+            self.camera.animate(delta_time);
+            if !self.camera.is_animating() {
+                // continue from where the move ended
+                self.controllers[self.controller].activate(&self.camera);
+            }
+        } else {
+            self.controllers[self.controller].update(&mut self.camera, &self.input, delta_time);
+            if self.controllers[self.controller].mouse_look() {
+                turn = match replayed_turn {
+                    Some(turn) => turn,
+                    None if replaying => (0.0, 0.0),
+                    None => self.mouse_look.take(delta_time),
+                };
+                self.camera.turn(turn.0, turn.1);
+            }
+        }
+        // mouse look is smoothed over ticks, so what it handed over is recorded rather than the motion
+        if turning && !replaying {
+            self.record_input(replay::InputEvent::Turn(turn.0, turn.1));
+        }
+        self.input.end_tick(delta_time);
+        self.debug_draw.update(delta_time);
+
+        let pose = self.camera.pose();
+        if let Some(recording) = &mut self.input_recording {
+            recording.end_tick(&pose);
+        }
+        let replay_finished = match &mut self.replay {
+            Some(replay) => {
+                replay.end_tick(&pose);
+                replay.is_finished()
+            }
+            None => false,
+        };
+        if replay_finished {
+            self.stop_replay();
+        }
+
+        if self.input.any_down() || self.camera.is_moving() {
+            self.request_redraw();
+        }
+
+        self.stats.add_update_time(start.elapsed());
+    }
+
+    // Anything that steers the camera, which takes over from scripted moves
+    fn has_user_input(&self, turning: bool) -> bool {
+        self.input.any_down() || self.input.any_axis() || turning
+    }
+
+    // Feeds the replay's events for this tick into the input, returns the turn it recorded
+    fn apply_replay_events(&mut self) -> Option<(f32, f32)> {
+        let events = match &mut self.replay {
+            Some(replay) => replay.take_events(),
+            None => return None,
+        };
+        let mut turn = None;
+        for event in events {
+            match event {
+                replay::InputEvent::Down(action) => {
+                    self.input.set_down(action, true);
+                }
+                replay::InputEvent::Up(action) => {
+                    self.input.set_down(action, false);
+                }
+                replay::InputEvent::MouseMotion(dx, dy) => self.input.add_mouse_motion(dx, dy),
+                replay::InputEvent::Scroll(lines) => self.input.add_scroll(lines),
+                replay::InputEvent::Turn(yaw, pitch) => turn = Some((yaw, pitch)),
+                replay::InputEvent::ReleaseAll => self.input.release_actions(),
+            }
+        }
+        turn
+    }
+
+    // Glides the camera so the box fills the view, keeping the direction it looks in
+    pub fn focus(&mut self, min: &Vector, max: &Vector) {
+        let orientation = self.camera.orientation;
+        self.frame(min, max, orientation);
+    }
+
+    // Switches projection and turns to look at the whole scene from the view's direction
+    pub fn set_view(&mut self, view: camera::View) {
+        self.camera.projection = view.projection();
+        let orientation = view.orientation().unwrap_or(self.camera.orientation);
+        let (min, max) = self.bounds;
+        self.frame(&min, &max, orientation);
+    }
+
+    fn frame(&mut self, min: &Vector, max: &Vector, orientation: quaternion::Quaternion) {
+        let forward = orientation.rotate(&Vector::new(0.0, 0.0, -1.0));
+        let position = self.controllers[self.controller].fit(&self.camera, &forward, min, max);
+        if self.camera.is_parallel() {
+            self.camera.view_height = controller::fit_view_height(&self.camera, min, max);
+        }
+        let pose = animation::Pose { position: position, orientation: orientation };
+        self.camera.move_to(&pose, FOCUS_DURATION, animation::Easing::EaseInOut);
+        self.request_redraw();
+    }
+
+    // Glides the camera to a pose, user input cancels the move
+    pub fn move_camera_to(&mut self, pose: &animation::Pose, duration: f32, easing: animation::Easing) {
+        self.camera.move_to(pose, duration, easing);
+        if duration <= 0.0 {
+            self.controllers[self.controller].activate(&self.camera);
+        }
+        self.request_redraw();
+    }
+
+    pub fn look_at(&mut self, eye: &Vector, target: &Vector, up: &Vector) {
+        self.camera.look_at(eye, target, up);
+        self.controllers[self.controller].activate(&self.camera);
+        self.request_redraw();
+    }
+
+    // Keeps an on demand loop drawing for a few more frames, call while animating
+    pub fn request_redraw(&mut self) {
+        self.redraw_frames = SETTLE_FRAMES;
+    }
+
+    pub fn needs_redraw(&self) -> bool {
+        self.redraw_frames > 0
+    }
+
+    // Whether nothing is drawn until input arrives, the simulation can pause meanwhile
+    pub fn is_idle(&self) -> bool {
+        self.recording.is_none()
+            && self.replay.is_none()
+            && !self.mouse_look.is_turning()
+            && !self.input.awaits_double_tap()
+            && self.pacer.mode == pacing::RenderMode::OnDemand
+            && !self.needs_redraw()
+    }
+
+    // Whether the event loop should draw this iteration and how it should wait
+    pub fn schedule_frame(&self) -> (bool, ControlFlow) {
+        if self.recording.is_some() {
+            // every frame is recorded, so neither sleep nor skip any
+            return (true, ControlFlow::Poll);
+        }
+        if self.replay.is_some() {
+            // the replay runs in real time whether or not anything moves
+            return (true, ControlFlow::Poll);
+        }
+        // an expired message stays on screen until the next frame
+        match self.pacer.schedule(self.needs_redraw() || self.status.has_expired()) {
+            (draw, ControlFlow::Wait) => match self.wake_up_time() {
+                Some(time) => (draw, ControlFlow::WaitUntil(time)),
+                None => (draw, ControlFlow::Wait),
+            },
+            schedule => schedule,
+        }
+    }
+
+    // The earliest something has to happen while idle
+    fn wake_up_time(&self) -> Option<Instant> {
+        let times = [
+            // a double tap is timed in ticks, which stop while nothing is drawn
+            if self.input.awaits_double_tap() { Some(Instant::now() + INPUT_TICK_INTERVAL) } else { None },
+            // check the config file
+            self.config_source.as_ref().map(|_| self.config_checked + CONFIG_POLL_INTERVAL),
+            // take the oldest message off the screen
+            self.status.next_expiry(),
+        ];
+        times.iter().filter_map(|time| *time).min()
+    }
+
+    pub fn set_render_mode(&mut self, mode: pacing::RenderMode) {
+        self.pacer.mode = mode;
+        self.request_redraw();
+    }
+
+    pub fn set_fps_cap(&mut self, fps_cap: Option<f32>) {
+        self.pacer.fps_cap = fps_cap;
+    }
+
+    // Falls back to the closest mode the backend can offer, see `present_mode` for the result
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+        self.requested_present_mode = mode;
+        let resolved = present::resolve_present_mode(mode, self.backend);
+        if resolved != mode {
+            self.status.push(format!("Present mode {:?} is not supported on {:?}, using {:?}", mode, self.backend, resolved));
+        }
+        if resolved != self.present_mode {
+            self.present_mode = resolved;
+            self.recreate_swapchain(self.size);
+            self.request_redraw();
+        }
+    }
+
+    // Mode the swapchain asked for, a driver without it presents with Fifo instead and wgpu
+    // doesn't say so
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.present_mode
+    }
+
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.set_present_mode(if vsync { wgpu::PresentMode::Fifo } else { wgpu::PresentMode::Mailbox });
+    }
+
+    pub fn stats(&self) -> &stats::Stats {
+        &self.stats
+    }
+
+    // Writes the frame history to the working directory as CSV and JSON
+    pub fn export_stats(&mut self) {
+        let timestamp = utils::timestamp();
+        for extension in &["csv", "json"] {
+            let path = format!("stats-{}.{}", timestamp, extension);
+            match self.stats.export(&path) {
+                Ok(()) => self.status.push(format!("Exported frame statistics to {}", path)),
+                Err(err) => self.status.push(format!("Failed to export frame statistics to {}: {}", path, err)),
+            }
+        }
+    }
+
+    pub fn debug_draw(&mut self) -> &mut debug_draw::DebugDraw {
+        &mut self.debug_draw
     }
 
-    pub fn render(&mut self, _event: Event<()>) {
+    pub fn text(&mut self) -> &mut text::TextRenderer {
+        &mut self.text
+    }
+
+    // Switches to the next camera controller, which picks up the camera where it is
+    pub fn next_controller(&mut self, window: &Window) {
+        self.controller = (self.controller + 1) % self.controllers.len();
+        let controller = &mut self.controllers[self.controller];
+        controller.activate(&self.camera);
+        let (name, mouse_look) = (controller.name(), controller.mouse_look());
+        if !mouse_look {
+            self.set_mouse_grabbed(window, false);
+        }
+        self.status.push(format!("Camera controller: {}", name));
+    }
+
+    // Says so on screen when the window won't grab or release the cursor
+    fn set_mouse_grabbed(&mut self, window: &Window, grabbed: bool) {
+        if let Err(err) = self.mouse_look.set_grabbed(window, grabbed) {
+            self.status.push(format!("Failed to {} the cursor: {}", if grabbed { "grab" } else { "release" }, err));
+        }
+    }
+
+    pub fn mouse_look(&mut self) -> &mut mouse_look::MouseLook {
+        &mut self.mouse_look
+    }
+
+    pub fn camera(&self) -> &camera::Camera {
+        &self.camera
+    }
+
+    pub fn scene_transform(&self) -> &types::Transform {
+        &self.scene_transform
+    }
+
+    pub fn set_scene_transform(&mut self, transform: types::Transform) {
+        self.scene_transform = transform;
+    }
+
+    // `alpha` is how far the frame lies between the previous and the latest update
+    pub fn render(&mut self, window: &Window, alpha: f32) {
+        self.update_depth_mode();
+        self.camera.set_interpolation(alpha);
+        self.camera.begin_frame(self.size.width, self.size.height);
+        if self.camera.take_history_invalidated() {
+            self.taa.invalidate();
+        }
+        self.submit_uniform_data();
+
+        let encode_start = Instant::now();
         let frame = self.swapchain.get_next_texture().expect("Timeout when aquiring next swapchain texture");
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: None,
         });
 
+        let mut scene_stats = stats::FrameStats::default();
+        self.encode_scene_pass(&mut encoder, &self.targets, &mut scene_stats);
+        self.stats.current().merge(&scene_stats);
+
+        self.taa.resolve(&self.device, &mut encoder, &self.presented.view, self.stats.current());
+
+        self.grid.render(&self.device, &mut encoder, &self.presented.view, &self.targets.depth_view, &self.camera.view_projection(), self.stats.current());
+
+        let debug_vertices = self.debug_draw.take_vertices();
+        if self.debug_draw.enabled {
+            self.debug_renderer.render(&self.device, &mut encoder, &self.presented.view, &self.targets.depth_view, &self.bind_group, &debug_vertices, self.stats.current());
+        }
+
+        if self.stats.overlay {
+            self.stats.draw_overlay(&mut self.text, self.size.width);
+        }
+        self.status.draw(&mut self.text, self.size.height);
+        self.text.render(&self.device, &mut encoder, &self.presented.view, self.size.width, self.size.height, &self.camera.view_projection(), self.stats.current());
+
+        let mut camera_moved = false;
         {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                    attachment: &frame.view,
-                    resolve_target: None,
-                    load_op: wgpu::LoadOp::Clear,
-                    store_op: wgpu::StoreOp::Store,
-                    clear_color: CLEAR_COLOR,
-                }],
-                depth_stencil_attachment: None,
+            let camera = &mut self.camera;
+            let toggles = ui::RendererToggles {
+                taa: &mut self.taa.enabled,
+                grid: &mut self.grid.enabled,
+                debug_lines: &mut self.debug_draw.enabled,
+                text: &mut self.text.enabled,
+                visualization_mode: &mut self.visualization_mode,
+                present_mode: self.present_mode,
+                render_mode: self.pacer.mode,
+            };
+            let (actions, rebinding, status, stats) = (&mut self.actions, &mut self.rebinding, &mut self.status, &self.stats);
+            self.ui.render(window, &self.device, &mut encoder, &self.presented.view, |ui| {
+                camera_moved = ui::camera_panel(ui, camera);
+                ui::controls_panel(ui, actions, rebinding, status);
+                ui::timings_panel(ui, stats);
+                ui::renderer_panel(ui, toggles);
             });
+        }
+        self.presented.present(&mut encoder, &frame.view, self.stats.current());
+        if camera_moved {
+            // an orbit would otherwise swing back around its old target
+            self.controllers[self.controller].activate(&self.camera);
+        }
+        // the controls panel may have cleared or reset bindings
+        self.refresh_held();
+        self.camera.jitter_enabled = self.taa.enabled;
+
+        let command_buffer = encoder.finish();
+        let encode_time = encode_start.elapsed();
+
+        let submit_start = Instant::now();
+        self.queue.submit(&[command_buffer]);
+        self.stats.end_frame(encode_time, submit_start.elapsed());
+
+        if self.recording.is_some() {
+            self.record_frame();
+        }
+
+        self.pacer.frame_rendered();
+        self.redraw_frames = self.redraw_frames.saturating_sub(1);
+
+        self.camera.end_frame();
+    }
 
-            render_pass.set_pipeline(&self.pipeline);
-            render_pass.set_bind_group(0, &self.bind_group, &[]);
-            render_pass.set_index_buffer(&self.index_buffer, 0, 0);
-            render_pass.set_vertex_buffer(0, &self.vertex_buffer, 0, 0);
-            render_pass.draw_indexed(0..self.index_buffer_len, 0, 0..1);
+    // Writes the last presented frame to a PNG. With supersampling the view is instead rendered
+    // that many times larger and filtered down, which smooths edges in place of TAA but leaves
+    // out the debug lines, text and UI.
+    pub fn capture_screenshot<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let (width, height) = (self.size.width.max(1), self.size.height.max(1));
+        let largest_scale = (capture::MAX_CAPTURE_SIZE / width.max(height)).max(1);
+        let scale = self.config.capture.screenshot_supersample.max(1).min(largest_scale);
+        if scale == 1 {
+            let pixels = self.presented.read(&self.device, &self.queue);
+            return capture::write_png(path, self.presented.width, self.presented.height, &pixels);
         }
 
+        let uniforms = self.camera.still_uniforms();
+        let pixels = self.render_offscreen(width * scale, height * scale, &uniforms);
+        let pixels = capture::downsample(&pixels, width * scale, height * scale, scale);
+        capture::write_png(path, width, height, &pixels)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // Simulated time per frame while recording, the event loop should advance by this instead of real time
+    pub fn recording_frame_dt(&self) -> Option<f32> {
+        self.recording.as_ref().map(|recording| recording.frame_dt)
+    }
+
+    pub fn start_recording(&mut self) {
+        if self.recording.is_some() {
+            return;
+        }
+        let settings = self.config.capture.recording_settings(self.recording_output.clone());
+        match recording::Recording::start(&settings) {
+            Ok(recording) => {
+                self.status.push(format!("Recording at {}x{}, {} fps", recording.width, recording.height, settings.fps));
+                self.recording = Some(recording);
+            }
+            Err(err) => self.status.push(format!("Failed to start recording: {}", err)),
+        }
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            let location = recording.location().map(|path| path.display().to_string());
+            match recording.finish() {
+                Ok(frames) => match location {
+                    Some(location) => self.status.push(format!("Recorded {} frames to {}", frames, location)),
+                    None => self.status.push(format!("Recorded {} frames", frames)),
+                },
+                Err(err) => self.status.push(format!("Failed to finish recording: {}", err)),
+            }
+            self.request_redraw();
+        }
+    }
+
+    pub fn toggle_recording(&mut self) {
+        if self.recording.is_some() {
+            self.stop_recording();
+        } else {
+            self.start_recording();
+        }
+    }
+
+    pub fn is_recording_input(&self) -> bool {
+        self.input_recording.is_some()
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    // Records input from the next tick on, with the camera where it is and fresh controllers.
+    // Changes from outside the input, such as the UI or a config reload, are not recorded and
+    // show up as a divergence when replayed.
+    pub fn start_input_recording(&mut self) {
+        if self.replay.is_some() {
+            self.status.push("Can't record input during a replay");
+            return;
+        }
+        self.reset_simulation();
+        self.input_recording = Some(replay::InputRecording::new(timestep::FIXED_DT, self.start_state()));
+        self.status.push("Recording input");
+    }
+
+    // Saves the input recorded so far to the working directory
+    pub fn stop_input_recording(&mut self) {
+        let recording = match self.input_recording.take() {
+            Some(recording) => recording,
+            None => return,
+        };
+        let path = PathBuf::from(format!("inputs-{}.txt", utils::timestamp()));
+        match recording.save(&path) {
+            Ok(()) => {
+                self.status.push(format!("Saved {} input events over {} ticks to {}", recording.events(), recording.ticks, path.display()));
+                self.last_input_recording = Some(path);
+            }
+            Err(err) => self.status.push(format!("Failed to save input recording to {}: {}", path.display(), err)),
+        }
+    }
+
+    pub fn toggle_input_recording(&mut self) {
+        if self.input_recording.is_some() {
+            self.stop_input_recording();
+        } else {
+            self.start_input_recording();
+        }
+    }
+
+    // Puts the camera back where the recording started and plays its input back tick by tick,
+    // any key press other than the replay key is ignored until it ends
+    pub fn replay_inputs<P: AsRef<Path>>(&mut self, window: &Window, path: P) -> io::Result<()> {
+        let recording = replay::InputRecording::load(&path)?;
+        self.stop_input_recording();
+        if recording.tick_dt != timestep::FIXED_DT {
+            self.status.push(format!("Recorded at {} s per tick but replaying at {}, the camera will take another path", recording.tick_dt, timestep::FIXED_DT));
+        }
+
+        let start = recording.start;
+        let size = PhysicalSize::new(start.size.0, start.size.1);
+        if size != self.size {
+            window.set_inner_size(size);
+        }
+        self.set_mouse_grabbed(window, false);
+        self.reset_simulation();
+        self.camera.set_pose(&start.pose);
+        self.camera.projection = start.projection;
+        self.camera.view_height = start.view_height;
+        self.camera.fov = start.fov;
+        self.camera.aspect_ratio = start.aspect_ratio;
+        self.camera.speed = start.speed;
+        self.camera.rotation_speed = start.rotation_speed;
+        self.controller = start.controller.min(self.controllers.len() - 1);
+        self.controllers[self.controller].activate(&self.camera);
+        self.camera.invalidate_history();
+
+        self.replay = Some(replay::Replay::new(recording));
+        self.replay_outcome = None;
+        self.status.push(format!("Replaying {}", path.as_ref().display()));
+        Ok(())
+    }
+
+    // Ends the replay early or once it ran out, and hands the input back to the user
+    pub fn stop_replay(&mut self) {
+        let replay = match self.replay.take() {
+            Some(replay) => replay,
+            None => return,
+        };
+        let outcome = replay.outcome();
+        match outcome.diverged_at {
+            Some(tick) => self.status.push(format!("Replay diverged from the recording at tick {}", tick)),
+            None if outcome.ticks < replay.recording().ticks => {
+                self.status.push(format!("Replay stopped after {} of {} ticks", outcome.ticks, replay.recording().ticks))
+            }
+            None => self.status.push(format!("Replayed {} ticks, the camera followed the recording exactly", outcome.ticks)),
+        }
+        self.replay_outcome = Some(outcome);
+        self.input.release_actions();
+        self.refresh_held();
+        self.request_redraw();
+    }
+
+    // How the last replay went, once it has ended
+    pub fn take_replay_outcome(&mut self) -> Option<replay::Outcome> {
+        self.replay_outcome.take()
+    }
+
+    fn start_state(&self) -> replay::StartState {
+        replay::StartState {
+            pose: self.camera.pose(),
+            projection: self.camera.projection,
+            view_height: self.camera.view_height,
+            fov: self.camera.fov,
+            aspect_ratio: self.camera.aspect_ratio,
+            speed: self.camera.speed,
+            rotation_speed: self.camera.rotation_speed,
+            controller: self.controller,
+            size: (self.size.width, self.size.height),
+        }
+    }
+
+    // Clears what a tick carries over besides the camera pose, so recording and replay start
+    // from the same state
+    fn reset_simulation(&mut self) {
+        self.camera.stop_animation();
+        self.controllers = Engine::create_controllers();
+        self.controllers[self.controller].activate(&self.camera);
+        self.input.release_actions();
+    }
+
+    // Renders the current view at the recording resolution, without debug overlays
+    fn record_frame(&mut self) {
+        let (width, height) = match &self.recording {
+            Some(recording) => (recording.width, recording.height),
+            None => return,
+        };
+
+        let uniforms = self.still_uniforms(width, height);
+        let pixels = self.render_offscreen(width, height, &uniforms);
+        let result = self.recording.as_mut().map(|recording| recording.write_frame(&pixels));
+        if let Some(Err(err)) = result {
+            self.status.push(format!("Failed to write recorded frame: {}", err));
+            self.stop_recording();
+        }
+    }
+
+    // Renders the current view into an image of any size by splitting it into tiles,
+    // the PNG is written one row of tiles at a time so the poster never has to fit in memory
+    pub fn capture_poster<P: AsRef<Path>>(&mut self, path: P, width: u32, height: u32) -> io::Result<()> {
+        if width == 0 || height == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "poster size must not be zero"));
+        }
+        let view_projection = utils::array_to_matrix4(&self.still_uniforms(width, height).view_projection);
+
+        let mut writer = capture::png_writer(path, width, height)?;
+        let mut stream = writer.stream_writer();
+        for row in poster::tile_rows(width, height) {
+            let mut band = vec![0; (width * row[0].height * 4) as usize];
+            for tile in row.iter() {
+                let projection = tile.projection(width, height) * view_projection;
+                let uniforms = self.camera.still_uniforms_with(utils::matrix4_to_array(projection));
+                let (render_width, render_height) = tile.render_size();
+                let pixels = self.render_offscreen(render_width, render_height, &uniforms);
+                tile.copy_into(&pixels, &mut band, width);
+            }
+            stream.write_all(&band)?;
+        }
+        stream.finish()?;
+        Ok(())
+    }
+
+    // Renders a full sphere around the camera position to an equirectangular PNG that is
+    // `height` pixels high, centred on the camera's heading with a level horizon
+    pub fn capture_panorama<P: AsRef<Path>>(&mut self, path: P, height: u32) -> io::Result<()> {
+        let height = height.max(1);
+        let width = height * 2;
+        // a 90° face is sampled at roughly the panorama's resolution at its edges
+        let face_size = height.min(capture::MAX_CAPTURE_SIZE);
+
+        // cube faces only meet up in perspective
+        let (fov, aspect_ratio, projection) = (self.camera.fov, self.camera.aspect_ratio, self.camera.projection);
+        self.camera.fov = 90.0;
+        self.camera.aspect_ratio = 1.0;
+        self.camera.projection = camera::Projection::Perspective;
+
+        let mut faces = Vec::new();
+        for face in panorama::FACES.iter() {
+            let view_projection = self.camera.level_view_projection(&face.rotation());
+            let uniforms = self.camera.still_uniforms_with(view_projection);
+            faces.push(self.render_offscreen(face_size, face_size, &uniforms));
+        }
+        let pixels = panorama::reproject(&faces, face_size, width, height);
+
+        self.camera.fov = fov;
+        self.camera.aspect_ratio = aspect_ratio;
+        self.camera.projection = projection;
+
+        panorama::write_png(path, width, height, &pixels)
+    }
+
+    // Uniforms of the current view for an image that can be shaped differently than the window
+    fn still_uniforms(&mut self, width: u32, height: u32) -> types::Uniforms {
+        let aspect_ratio = self.camera.aspect_ratio;
+        self.camera.aspect_ratio = width as f32 / height as f32;
+        let uniforms = self.camera.still_uniforms();
+        self.camera.aspect_ratio = aspect_ratio;
+        uniforms
+    }
+
+    // Renders the scene and grid into a new texture of any size and reads it back as RGBA8.
+    // The uniforms are left overwritten, the next `render` uploads the camera again.
+    fn render_offscreen(&mut self, width: u32, height: u32, uniforms: &types::Uniforms) -> Vec<u8> {
+        self.update_depth_mode();
+        let targets = targets::RenderTargets::new(&self.device, width, height, self.color_format, self.targets.samples());
+        let output = capture::create_output_texture(&self.device, width, height, self.color_format);
+        let output_view = output.create_default_view();
+
+        self.write_uniforms(uniforms);
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let mut capture_stats = stats::FrameStats::default();
+        self.encode_scene_pass(&mut encoder, &targets, &mut capture_stats);
+        encoder.copy_texture_to_texture(
+            wgpu::TextureCopyView {
+                texture: &targets.color,
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::TextureCopyView {
+                texture: &output,
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::Extent3d {
+                width: width,
+                height: height,
+                depth: 1,
+            },
+        );
+        self.grid.render(&self.device, &mut encoder, &output_view, &targets.depth_view, &uniforms.view_projection, &mut capture_stats);
         self.queue.submit(&[encoder.finish()]);
+
+        capture::read_texture_rgba(&self.device, &self.queue, &output, width, height)
+    }
+
+    // Rebuilds everything that depth tests once the camera switches depth convention
+    fn update_depth_mode(&mut self) {
+        let depth_mode = self.camera.depth_mode();
+        if depth_mode == self.depth_mode {
+            return;
+        }
+
+        self.depth_mode = depth_mode;
+        self.rebuild_scene_pipelines();
+        let grid_enabled = self.grid.enabled;
+        self.grid = grid::Grid::new(&self.device, self.color_format, depth_mode);
+        self.grid.enabled = grid_enabled;
+        self.debug_renderer = debug_draw::DebugRenderer::new(
+            &self.device,
+            &self.bind_group_layout,
+            Engine::create_vertex_buffer(types::VERTEX_SIZE as wgpu::BufferAddress),
+            self.color_format,
+            depth_mode,
+        );
+        // history was resolved against the old depth
+        self.taa.invalidate();
+    }
+
+    fn rebuild_scene_pipelines(&mut self) {
+        self.pipelines = visualization::ScenePipelines::new(
+            &self.device,
+            &self.bind_group_layout,
+            Engine::create_vertex_buffer(types::VERTEX_SIZE as wgpu::BufferAddress),
+            self.color_format,
+            self.targets.samples(),
+            self.depth_mode,
+        );
+    }
+
+    fn set_msaa_samples(&mut self, samples: u32) {
+        if samples == self.targets.samples() {
+            return;
+        }
+        self.targets = targets::RenderTargets::new(&self.device, self.size.width, self.size.height, self.color_format, samples);
+        self.taa.resize(&self.device, &self.targets);
+        self.rebuild_scene_pipelines();
+        self.request_redraw();
+    }
+
+    // With MSAA the scene is drawn multisampled, then its colour and velocity are resolved by
+    // the pass and its depth afterwards
+    fn encode_scene_pass(&self, encoder: &mut wgpu::CommandEncoder, targets: &targets::RenderTargets, stats: &mut stats::FrameStats) {
+        self.encode_scene_draws(encoder, targets, stats);
+        self.depth_resolve.encode(&self.device, encoder, targets);
+    }
+
+    fn encode_scene_draws(&self, encoder: &mut wgpu::CommandEncoder, targets: &targets::RenderTargets, stats: &mut stats::FrameStats) {
+        let (color, color_resolve, velocity, velocity_resolve, depth) = match &targets.multisampled {
+            Some(multisampled) => (
+                &multisampled.color_view,
+                Some(&targets.color_view),
+                &multisampled.velocity_view,
+                Some(&targets.velocity_view),
+                &multisampled.depth_view,
+            ),
+            None => (&targets.color_view, None, &targets.velocity_view, None, &targets.depth_view),
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[
+                wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: color,
+                    resolve_target: color_resolve,
+                    load_op: wgpu::LoadOp::Clear,
+                    store_op: wgpu::StoreOp::Store,
+                    clear_color: self.config.renderer.clear_color(),
+                },
+                wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: velocity,
+                    resolve_target: velocity_resolve,
+                    load_op: wgpu::LoadOp::Clear,
+                    store_op: wgpu::StoreOp::Store,
+                    clear_color: wgpu::Color::TRANSPARENT,
+                },
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                attachment: depth,
+                depth_load_op: wgpu::LoadOp::Clear,
+                depth_store_op: wgpu::StoreOp::Store,
+                clear_depth: self.depth_mode.clear_depth(),
+                stencil_load_op: wgpu::LoadOp::Clear,
+                stencil_store_op: wgpu::StoreOp::Store,
+                clear_stencil: 0,
+            }),
+        });
+
+        let surface_mode = match self.visualization_mode {
+            visualization::VisualizationMode::Wireframe => visualization::VisualizationMode::Shaded,
+            mode => mode,
+        };
+
+        render_pass.set_pipeline(self.pipelines.get(surface_mode));
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_index_buffer(&self.index_buffer, 0, 0);
+        render_pass.set_vertex_buffer(0, &self.vertex_buffer, 0, 0);
+        render_pass.draw_indexed(0..self.index_buffer_len, 0, 0..1);
+        stats.draw(self.index_buffer_len / 3);
+
+        if self.visualization_mode == visualization::VisualizationMode::Wireframe {
+            render_pass.set_pipeline(self.pipelines.get(visualization::VisualizationMode::Wireframe));
+            render_pass.set_index_buffer(&self.edge_index_buffer, 0, 0);
+            render_pass.draw_indexed(0..self.edge_index_buffer_len, 0, 0..1);
+            stats.draw(0);
+        }
     }
 
     pub fn window_resized(&mut self, size: PhysicalSize<u32>) {
+        self.size = size;
         self.recreate_swapchain(size);
+        self.targets = targets::RenderTargets::new(&self.device, size.width, size.height, self.color_format, self.targets.samples());
+        self.taa.resize(&self.device, &self.targets);
+        self.presented.resize(&self.device, size.width, size.height, self.color_format);
+        self.request_redraw();
         self.camera.aspect_ratio = size.width as f32 / size.height as f32;
         self.submit_uniform_data();
     }
 
     fn submit_uniform_data(&mut self) {
-        let temp_buffer = self.device.create_buffer_with_data(&self.camera.projection().as_bytes(), wgpu::BufferUsage::COPY_SRC);
+        let model = self.scene_transform.matrix();
+        let mut uniforms = self.camera.uniforms();
+        uniforms.model = model;
+        uniforms.prev_model = self.prev_scene_model;
+        self.prev_scene_model = model;
+        self.upload_uniforms(uniforms);
+    }
+
+    // Uniforms for a single image, where the scene doesn't move
+    fn write_uniforms(&mut self, uniforms: &types::Uniforms) {
+        let mut uniforms = *uniforms;
+        uniforms.model = self.scene_transform.matrix();
+        uniforms.prev_model = uniforms.model;
+        self.upload_uniforms(uniforms);
+    }
+
+    fn upload_uniforms(&mut self, mut uniforms: types::Uniforms) {
+        uniforms.params[2] = SCENE_MESH_ID as f32;
+        uniforms.params[3] = LIGHT_COUNT as f32;
+        let temp_buffer = self.device.create_buffer_with_data(&uniforms.as_bytes(), wgpu::BufferUsage::COPY_SRC);
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        encoder.copy_buffer_to_buffer(&temp_buffer, 0, &self.uniform_buffer, 0, 64);
+        encoder.copy_buffer_to_buffer(&temp_buffer, 0, &self.uniform_buffer, 0, types::UNIFORMS_SIZE as wgpu::BufferAddress);
         self.queue.submit(&[encoder.finish()]);
+        self.stats.current().upload(types::UNIFORMS_SIZE);
     }
 
     fn recreate_swapchain(&mut self, size: PhysicalSize<u32>) {
-        let swapchain_description = create_swapchain_description(size);
+        let swapchain_description = create_swapchain_description(size, self.present_mode, self.color_format);
         self.swapchain = self.device.create_swap_chain(&self.surface, &swapchain_description);
     }
 
@@ -382,19 +1638,29 @@ impl Engine {
                     format: wgpu::VertexFormat::Float3,
                     offset: 4 * 3,
                     shader_location: 1,
+                },
+                wgpu::VertexAttributeDescriptor {       // normal
+                    format: wgpu::VertexFormat::Float3,
+                    offset: 4 * 6,
+                    shader_location: 2,
+                },
+                wgpu::VertexAttributeDescriptor {       // uv
+                    format: wgpu::VertexFormat::Float2,
+                    offset: 4 * 9,
+                    shader_location: 3,
                 }
             ]
         }
     }
 }
 
-pub fn create_swapchain_description (size: PhysicalSize<u32>) -> wgpu::SwapChainDescriptor {
+pub fn create_swapchain_description (size: PhysicalSize<u32>, present_mode: wgpu::PresentMode, format: wgpu::TextureFormat) -> wgpu::SwapChainDescriptor {
     wgpu::SwapChainDescriptor {
         usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-        format: TEXTURE_FORMAT,
+        format: format,
         width: size.width,
         height: size.height,
-        present_mode: wgpu::PresentMode::Mailbox,
+        present_mode: present_mode,
     }
 }
 
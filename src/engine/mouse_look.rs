@@ -0,0 +1,78 @@
+use winit::error::ExternalError;
+use winit::window::Window;
+
+const SENSITIVITY: f32 = 0.0025;
+const SMOOTHING: f32 = 0.02;
+// Pending rotation below this is handed out at once instead of trickling on forever
+const SETTLED: f32 = 1e-5;
+
+// Turns raw mouse motion into camera rotation while the cursor is grabbed
+pub struct MouseLook {
+    // radians per pixel of mouse motion
+    pub sensitivity: f32,
+    pub invert_y: bool,
+    // seconds for motion to mostly catch up, 0 applies it immediately
+    pub smoothing: f32,
+    grabbed: bool,
+    // rotation received but not yet handed to the camera, in radians
+    pending: (f32, f32),
+}
+
+impl MouseLook {
+    pub fn new() -> MouseLook {
+        MouseLook {
+            sensitivity: SENSITIVITY,
+            invert_y: false,
+            smoothing: SMOOTHING,
+            grabbed: false,
+            pending: (0.0, 0.0),
+        }
+    }
+
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    // Grabs and hides the cursor so motion isn't stopped by the window border
+    pub fn set_grabbed(&mut self, window: &Window, grabbed: bool) -> Result<(), ExternalError> {
+        if grabbed == self.grabbed {
+            return Ok(());
+        }
+        window.set_cursor_grab(grabbed)?;
+        window.set_cursor_visible(!grabbed);
+        self.grabbed = grabbed;
+        self.pending = (0.0, 0.0);
+        Ok(())
+    }
+
+    // Whether motion is still waiting to be handed to the camera
+    pub fn is_turning(&self) -> bool {
+        self.pending != (0.0, 0.0)
+    }
+
+    pub fn add_motion(&mut self, dx: f64, dy: f64) {
+        if !self.grabbed {
+            return;
+        }
+        let dy = if self.invert_y { dy } else { -dy };
+        self.pending.0 += dx as f32 * self.sensitivity;
+        self.pending.1 += dy as f32 * self.sensitivity;
+    }
+
+    // Yaw and pitch to apply this tick. Smoothing hands out part of the pending rotation each
+    // tick, so the total always matches the mouse and doesn't depend on the tick rate.
+    pub fn take(&mut self, delta_time: f32) -> (f32, f32) {
+        let amount = if self.smoothing > 0.0 {
+            1.0 - (-delta_time / self.smoothing).exp()
+        } else {
+            1.0
+        };
+        let mut turn = (self.pending.0 * amount, self.pending.1 * amount);
+        if (self.pending.0 - turn.0).abs() < SETTLED && (self.pending.1 - turn.1).abs() < SETTLED {
+            turn = self.pending;
+        }
+        self.pending.0 -= turn.0;
+        self.pending.1 -= turn.1;
+        turn
+    }
+}
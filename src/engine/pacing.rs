@@ -0,0 +1,56 @@
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use winit::event_loop::ControlFlow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderMode {
+    // redraw as often as possible, or at the FPS cap
+    Continuous,
+    // redraw only while something on screen changes, sleep otherwise
+    OnDemand,
+}
+
+// Decides when the event loop draws and how it waits in between
+pub struct FramePacer {
+    pub mode: RenderMode,
+    pub fps_cap: Option<f32>,
+    last_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new(mode: RenderMode, fps_cap: Option<f32>) -> FramePacer {
+        FramePacer {
+            mode: mode,
+            fps_cap: fps_cap,
+            last_frame: Instant::now(),
+        }
+    }
+
+    // Whether to draw now, and how the event loop should wait afterwards
+    pub fn schedule(&self, dirty: bool) -> (bool, ControlFlow) {
+        match self.mode {
+            RenderMode::OnDemand if !dirty => (false, ControlFlow::Wait),
+            RenderMode::OnDemand => (true, ControlFlow::Poll),
+            RenderMode::Continuous => {
+                if let Some(period) = self.frame_period() {
+                    let next = self.last_frame + period;
+                    if Instant::now() < next {
+                        return (false, ControlFlow::WaitUntil(next));
+                    }
+                }
+                (true, ControlFlow::Poll)
+            }
+        }
+    }
+
+    pub fn frame_rendered(&mut self) {
+        self.last_frame = Instant::now();
+    }
+
+    fn frame_period(&self) -> Option<Duration> {
+        self.fps_cap
+            .filter(|fps| *fps > 0.0)
+            .map(|fps| Duration::from_secs_f32(1.0 / fps))
+    }
+}
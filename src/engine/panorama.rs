@@ -0,0 +1,126 @@
+use std::f32::consts::PI;
+use std::io;
+use std::path::Path;
+use glm::{mat4, Matrix4};
+
+use super::capture::png_writer;
+
+const BYTES_PER_PIXEL: usize = 4;
+
+// One 90° face of the cube around the camera, as basis vectors in camera view space
+// where the camera looks down -z with +y up
+pub struct Face {
+    forward: [f32; 3],
+    up: [f32; 3],
+    right: [f32; 3],
+}
+
+pub const FACES: [Face; 6] = [
+    // front, right, back, left
+    Face { forward: [0.0, 0.0, -1.0], up: [0.0, 1.0, 0.0], right: [1.0, 0.0, 0.0] },
+    Face { forward: [1.0, 0.0, 0.0], up: [0.0, 1.0, 0.0], right: [0.0, 0.0, 1.0] },
+    Face { forward: [0.0, 0.0, 1.0], up: [0.0, 1.0, 0.0], right: [-1.0, 0.0, 0.0] },
+    Face { forward: [-1.0, 0.0, 0.0], up: [0.0, 1.0, 0.0], right: [0.0, 0.0, -1.0] },
+    // up, down
+    Face { forward: [0.0, 1.0, 0.0], up: [0.0, 0.0, 1.0], right: [1.0, 0.0, 0.0] },
+    Face { forward: [0.0, -1.0, 0.0], up: [0.0, 0.0, -1.0], right: [1.0, 0.0, 0.0] },
+];
+
+fn dot(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+impl Face {
+    // Turns the camera view so this face is straight ahead
+    pub fn rotation(&self) -> Matrix4<f32> {
+        let (r, u, f) = (&self.right, &self.up, &self.forward);
+        mat4(
+            r[0], u[0], -f[0], 0.0,
+            r[1], u[1], -f[1], 0.0,
+            r[2], u[2], -f[2], 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        )
+    }
+}
+
+// Resamples six square RGBA8 faces, in the order of FACES, to an equirectangular image whose
+// centre looks along the camera's heading
+pub fn reproject(faces: &[Vec<u8>], face_size: u32, width: u32, height: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(width as usize * height as usize * BYTES_PER_PIXEL);
+    for y in 0..height {
+        let latitude = PI * 0.5 - (y as f32 + 0.5) / height as f32 * PI;
+        for x in 0..width {
+            let longitude = (x as f32 + 0.5) / width as f32 * 2.0 * PI - PI;
+            let direction = [
+                latitude.cos() * longitude.sin(),
+                latitude.sin(),
+                -latitude.cos() * longitude.cos(),
+            ];
+
+            let (index, face) = FACES.iter().enumerate()
+                .max_by(|a, b| dot(&a.1.forward, &direction).partial_cmp(&dot(&b.1.forward, &direction)).unwrap())
+                .unwrap();
+            let depth = dot(&face.forward, &direction);
+            let u = (dot(&face.right, &direction) / depth + 1.0) * 0.5 * face_size as f32;
+            let v = (1.0 - dot(&face.up, &direction) / depth) * 0.5 * face_size as f32;
+            out.extend_from_slice(&sample(&faces[index], face_size, u, v));
+        }
+    }
+    out
+}
+
+// Bilinear lookup at texel coordinates, clamped to the face
+fn sample(pixels: &[u8], size: u32, u: f32, v: f32) -> [u8; 4] {
+    let max = (size - 1) as f32;
+    let x = (u - 0.5).max(0.0).min(max);
+    let y = (v - 0.5).max(0.0).min(max);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(size as usize - 1), (y0 + 1).min(size as usize - 1));
+    let (fx, fy) = (x.fract(), y.fract());
+
+    let texel = |x: usize, y: usize, channel: usize| pixels[(y * size as usize + x) * BYTES_PER_PIXEL + channel] as f32;
+    let mut color = [0; 4];
+    for channel in 0..4 {
+        let top = texel(x0, y0, channel) * (1.0 - fx) + texel(x1, y0, channel) * fx;
+        let bottom = texel(x0, y1, channel) * (1.0 - fx) + texel(x1, y1, channel) * fx;
+        color[channel] = (top * (1.0 - fy) + bottom * fy).round() as u8;
+    }
+    color
+}
+
+// Writes the panorama with Google Photo Sphere metadata, which panorama viewers use to detect
+// it. The faces are level, so the metadata leaves out any pose.
+pub fn write_png<P: AsRef<Path>>(path: P, width: u32, height: u32, pixels: &[u8]) -> io::Result<()> {
+    let mut writer = png_writer(path, width, height)?;
+    writer.write_chunk(*b"iTXt", &xmp_chunk(width, height))?;
+    writer.write_image_data(pixels)?;
+    Ok(())
+}
+
+fn xmp_chunk(width: u32, height: u32) -> Vec<u8> {
+    let xmp = format!(
+        concat!(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">",
+            "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+            "<rdf:Description rdf:about=\"\" xmlns:GPano=\"http://ns.google.com/photos/1.0/panorama/\">",
+            "<GPano:ProjectionType>equirectangular</GPano:ProjectionType>",
+            "<GPano:UsePanoramaViewer>True</GPano:UsePanoramaViewer>",
+            "<GPano:FullPanoWidthPixels>{width}</GPano:FullPanoWidthPixels>",
+            "<GPano:FullPanoHeightPixels>{height}</GPano:FullPanoHeightPixels>",
+            "<GPano:CroppedAreaImageWidthPixels>{width}</GPano:CroppedAreaImageWidthPixels>",
+            "<GPano:CroppedAreaImageHeightPixels>{height}</GPano:CroppedAreaImageHeightPixels>",
+            "<GPano:CroppedAreaLeftPixels>0</GPano:CroppedAreaLeftPixels>",
+            "<GPano:CroppedAreaTopPixels>0</GPano:CroppedAreaTopPixels>",
+            "</rdf:Description>",
+            "</rdf:RDF>",
+            "</x:xmpmeta>",
+        ),
+        width = width,
+        height = height,
+    );
+
+    // keyword, compression flag and method, empty language tag and translated keyword
+    let mut data = b"XML:com.adobe.xmp\0\0\0\0\0".to_vec();
+    data.extend_from_slice(xmp.as_bytes());
+    data
+}
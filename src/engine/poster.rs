@@ -0,0 +1,124 @@
+use glm::{mat4, Matrix4};
+
+// Size of the part of the poster each tile contributes
+pub const TILE_SIZE: u32 = 2048;
+// Extra pixels rendered around every tile and cropped away, so effects that look at
+// neighbouring pixels see the same surroundings as in one big render
+pub const TILE_OVERLAP: u32 = 16;
+
+const BYTES_PER_PIXEL: u32 = 4;
+
+// A rectangle of the poster in pixels, rows grow downwards
+#[derive(Debug, Clone, Copy)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Tiles covering a `width` by `height` poster, grouped into rows from the top
+pub fn tile_rows(width: u32, height: u32) -> Vec<Vec<Tile>> {
+    (0..height).step_by(TILE_SIZE as usize).map(|y| {
+        (0..width).step_by(TILE_SIZE as usize).map(|x| Tile {
+            x: x,
+            y: y,
+            width: TILE_SIZE.min(width - x),
+            height: TILE_SIZE.min(height - y),
+        }).collect()
+    }).collect()
+}
+
+impl Tile {
+    // Size of the offscreen render, including the overlap on every side
+    pub fn render_size(&self) -> (u32, u32) {
+        (self.width + 2 * TILE_OVERLAP, self.height + 2 * TILE_OVERLAP)
+    }
+
+    // Clip space transform that zooms the full poster projection onto this tile, applied
+    // before the perspective divide so the sub-frustum shares the original eye and planes
+    pub fn projection(&self, poster_width: u32, poster_height: u32) -> Matrix4<f32> {
+        let overlap = TILE_OVERLAP as f32;
+        let (width, height) = (poster_width as f32, poster_height as f32);
+        let left = (self.x as f32 - overlap) / width * 2.0 - 1.0;
+        let right = ((self.x + self.width) as f32 + overlap) / width * 2.0 - 1.0;
+        let top = 1.0 - (self.y as f32 - overlap) / height * 2.0;
+        let bottom = 1.0 - ((self.y + self.height) as f32 + overlap) / height * 2.0;
+
+        let scale_x = 2.0 / (right - left);
+        let scale_y = 2.0 / (top - bottom);
+        mat4(
+            scale_x, 0.0, 0.0, 0.0,
+            0.0, scale_y, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            -(right + left) / (right - left), -(top + bottom) / (top - bottom), 0.0, 1.0,
+        )
+    }
+
+    // Crops the overlap off a rendered tile and copies it into a band of poster rows
+    // starting at this tile's row
+    pub fn copy_into(&self, pixels: &[u8], band: &mut [u8], poster_width: u32) {
+        let (render_width, _) = self.render_size();
+        let row_bytes = (self.width * BYTES_PER_PIXEL) as usize;
+        for row in 0..self.height {
+            let src = (((row + TILE_OVERLAP) * render_width + TILE_OVERLAP) * BYTES_PER_PIXEL) as usize;
+            let dst = ((row * poster_width + self.x) * BYTES_PER_PIXEL) as usize;
+            band[dst..dst + row_bytes].copy_from_slice(&pixels[src..src + row_bytes]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::vec4;
+
+    #[test]
+    fn tiles_cover_the_poster() {
+        let rows = tile_rows(5000, 3000);
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| row.len() == 3));
+
+        let last = rows[1][2];
+        assert_eq!((last.x, last.y, last.width, last.height), (4096, 2048, 904, 952));
+        let area: u32 = rows.iter().flatten().map(|tile| tile.width * tile.height).sum();
+        assert_eq!(area, 5000 * 3000);
+    }
+
+    #[test]
+    fn projection_frames_the_tile_and_its_overlap() {
+        let tile = Tile { x: 2048, y: 0, width: 2048, height: 2048 };
+        let projection = tile.projection(4096, 4096);
+        let to_ndc = |pixel: f32, size: f32| pixel / size * 2.0 - 1.0;
+
+        // the corners of the overlap land on the corners of the render
+        let top_left = projection * vec4(to_ndc(2048.0 - 16.0, 4096.0), -to_ndc(-16.0, 4096.0), 0.5, 1.0);
+        let bottom_right = projection * vec4(to_ndc(4096.0 + 16.0, 4096.0), -to_ndc(2048.0 + 16.0, 4096.0), 0.5, 1.0);
+        for (actual, expected) in [(top_left.x, -1.0), (top_left.y, 1.0), (bottom_right.x, 1.0), (bottom_right.y, -1.0)].iter() {
+            assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+        }
+        // depth and w are left alone
+        assert_eq!((top_left.z, top_left.w), (0.5, 1.0));
+    }
+
+    #[test]
+    fn copy_into_crops_the_overlap() {
+        // a 2x2 tile one pixel from the left of a 4 pixel wide poster
+        let tile = Tile { x: 1, y: 0, width: 2, height: 2 };
+        let (render_width, render_height) = tile.render_size();
+        let mut pixels = vec![0u8; (render_width * render_height * BYTES_PER_PIXEL) as usize];
+        for y in 0..render_height {
+            for x in 0..render_width {
+                let index = ((y * render_width + x) * BYTES_PER_PIXEL) as usize;
+                pixels[index..index + 4].copy_from_slice(&[x as u8, y as u8, 0, 255]);
+            }
+        }
+
+        let mut band = vec![0u8; (4 * 2 * BYTES_PER_PIXEL) as usize];
+        tile.copy_into(&pixels, &mut band, 4);
+        assert_eq!(band, vec![
+            0, 0, 0, 0, 16, 16, 0, 255, 17, 16, 0, 255, 0, 0, 0, 0,
+            0, 0, 0, 0, 16, 17, 0, 255, 17, 17, 0, 255, 0, 0, 0, 0,
+        ]);
+    }
+}
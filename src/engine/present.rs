@@ -0,0 +1,35 @@
+use wgpu::{Backend, PresentMode};
+
+pub const PRESENT_MODES: [PresentMode; 3] = [PresentMode::Fifo, PresentMode::Mailbox, PresentMode::Immediate];
+
+// wgpu 0.5 doesn't expose the surface capabilities, so this is only what each backend can
+// offer at all, not what this driver does. Fifo is the only mode every driver has, a swapchain
+// asking for one the driver lacks gets Fifo from wgpu without being told.
+pub fn supported_present_modes(backend: Backend) -> &'static [PresentMode] {
+    match backend {
+        Backend::Vulkan | Backend::Dx12 => &[PresentMode::Fifo, PresentMode::Mailbox, PresentMode::Immediate],
+        Backend::Metal | Backend::Dx11 => &[PresentMode::Fifo, PresentMode::Immediate],
+        _ => &[PresentMode::Fifo],
+    }
+}
+
+// The requested mode if the backend can offer it, otherwise the closest one that it can. The
+// result is still only a request, see `supported_present_modes`.
+pub fn resolve_present_mode(requested: PresentMode, backend: Backend) -> PresentMode {
+    let fallbacks: &[PresentMode] = match requested {
+        // both avoid waiting on vsync
+        PresentMode::Mailbox => &[PresentMode::Mailbox, PresentMode::Immediate, PresentMode::Fifo],
+        PresentMode::Immediate => &[PresentMode::Immediate, PresentMode::Mailbox, PresentMode::Fifo],
+        _ => &[PresentMode::Fifo],
+    };
+    let supported = supported_present_modes(backend);
+    fallbacks.iter()
+        .find(|mode| supported.contains(mode))
+        .cloned()
+        .unwrap_or(PresentMode::Fifo)
+}
+
+pub fn next_present_mode(mode: PresentMode) -> PresentMode {
+    let index = PRESENT_MODES.iter().position(|m| *m == mode).unwrap_or(0);
+    PRESENT_MODES[(index + 1) % PRESENT_MODES.len()]
+}
@@ -0,0 +1,286 @@
+use glm::{mat4, Matrix4};
+
+use super::types::Vector;
+
+// Below this the quaternions are close enough that slerp falls back to a plain blend
+const SLERP_LINEAR_THRESHOLD: f32 = 0.9995;
+
+// Unit quaternion for orientations, w is the scalar part
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quaternion {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Quaternion {
+        Quaternion { x: x, y: y, z: z, w: w }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    // Rotation of `angle` radians counter-clockwise around `axis`, seen from where it points
+    pub fn from_axis_angle(axis: &Vector, angle: f32) -> Quaternion {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        Quaternion::new(axis.x * sin, axis.y * sin, axis.z * sin, cos)
+    }
+
+    // The axis is +x for the identity, which has no rotation to speak of
+    pub fn to_axis_angle(&self) -> (Vector, f32) {
+        let q = self.normalize();
+        let w = q.w.max(-1.0).min(1.0);
+        let sin = (1.0 - w * w).sqrt();
+        if sin < 1e-6 {
+            return (Vector::new(1.0, 0.0, 0.0), 0.0);
+        }
+        (Vector::new(q.x / sin, q.y / sin, q.z / sin), 2.0 * w.acos())
+    }
+
+    // Radians around x, then y, then z, as in the matrix Rx * Ry * Rz
+    pub fn from_euler(euler: &Vector) -> Quaternion {
+        Quaternion::from_axis_angle(&Vector::new(1.0, 0.0, 0.0), euler.x)
+            * Quaternion::from_axis_angle(&Vector::new(0.0, 1.0, 0.0), euler.y)
+            * Quaternion::from_axis_angle(&Vector::new(0.0, 0.0, 1.0), euler.z)
+    }
+
+    // Inverse of `from_euler`, y is kept within [-90°, 90°]
+    pub fn to_euler(&self) -> Vector {
+        let m = self.to_rows();
+        let y = m[0][2].max(-1.0).min(1.0).asin();
+        if m[0][2].abs() < 0.99999 {
+            Vector::new((-m[1][2]).atan2(m[2][2]), y, (-m[0][1]).atan2(m[0][0]))
+        } else {
+            // gimbal lock, x and z turn around the same axis so it all goes into x
+            Vector::new(m[2][1].atan2(m[1][1]), y, 0.0)
+        }
+    }
+
+    // Camera convention: yaw turns right around +y away from looking down -z, pitch looks up
+    pub fn from_yaw_pitch(yaw: f32, pitch: f32) -> Quaternion {
+        Quaternion::from_axis_angle(&Vector::new(0.0, 1.0, 0.0), -yaw)
+            * Quaternion::from_axis_angle(&Vector::new(1.0, 0.0, 0.0), pitch)
+    }
+
+    // Inverse of `from_yaw_pitch`, any roll is dropped
+    pub fn to_yaw_pitch(&self) -> (f32, f32) {
+        let forward = self.rotate(&Vector::new(0.0, 0.0, -1.0));
+        let flat = (forward.x * forward.x + forward.z * forward.z).sqrt();
+        let yaw = if flat > 1e-6 {
+            forward.x.atan2(-forward.z)
+        } else {
+            // straight up or down, the heading is where the top of the view points
+            let up = self.rotate(&Vector::new(0.0, 1.0, 0.0)) * -forward.y.signum();
+            up.x.atan2(-up.z)
+        };
+        (yaw, forward.y.atan2(flat))
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn normalize(&self) -> Quaternion {
+        let length = self.dot(self).sqrt();
+        if length > 0.0 {
+            Quaternion::new(self.x / length, self.y / length, self.z / length, self.w / length)
+        } else {
+            Quaternion::identity()
+        }
+    }
+
+    // The opposite rotation, for unit quaternions
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn rotate(&self, v: &Vector) -> Vector {
+        let u = Vector::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        *v + t * self.w + u.cross(&t)
+    }
+
+    // Constant speed rotation from `self` at 0 to `to` at 1, the short way round
+    pub fn slerp(&self, to: &Quaternion, t: f32) -> Quaternion {
+        let mut to = *to;
+        let mut cos = self.dot(&to);
+        if cos < 0.0 {
+            to = Quaternion::new(-to.x, -to.y, -to.z, -to.w);
+            cos = -cos;
+        }
+
+        let (from_weight, to_weight) = if cos > SLERP_LINEAR_THRESHOLD {
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Quaternion::new(
+            self.x * from_weight + to.x * to_weight,
+            self.y * from_weight + to.y * to_weight,
+            self.z * from_weight + to.z * to_weight,
+            self.w * from_weight + to.w * to_weight,
+        ).normalize()
+    }
+
+    pub fn to_matrix(&self) -> Matrix4<f32> {
+        let m = self.to_rows();
+        // mat4 takes columns
+        mat4(
+            m[0][0], m[1][0], m[2][0], 0.0,
+            m[0][1], m[1][1], m[2][1], 0.0,
+            m[0][2], m[1][2], m[2][2], 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        )
+    }
+
+    fn to_rows(&self) -> [[f32; 3]; 3] {
+        let Quaternion { x, y, z, w } = self.normalize();
+        [
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w)],
+            [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w)],
+            [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y)],
+        ]
+    }
+}
+
+impl std::ops::Mul for Quaternion {
+    type Output = Quaternion;
+
+    // Applies `other` first, then `self`
+    fn mul(self, other: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+            self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use glm::vec4;
+
+    use super::*;
+    use super::super::types::Transform;
+    use super::super::utils::{transform, trasform, rotation, matrix4_to_array};
+
+    const EPSILON: f32 = 1e-5;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < EPSILON, "{} != {}", a, b);
+    }
+
+    fn assert_vector_near(a: &Vector, b: &Vector) {
+        assert!(a.distance(b) < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    // q and -q are the same rotation
+    fn assert_rotation_near(a: &Quaternion, b: &Quaternion) {
+        assert_near(a.dot(b).abs(), 1.0);
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        let angles = [
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(0.3, -0.7, 1.1),
+            Vector::new(-2.5, 1.2, -3.0),
+            Vector::new(1.0, -1.5, 0.25),
+        ];
+        for euler in &angles {
+            let q = Quaternion::from_euler(euler);
+            assert_vector_near(&q.to_euler(), euler);
+            assert_rotation_near(&Quaternion::from_euler(&q.to_euler()), &q);
+        }
+    }
+
+    #[test]
+    fn euler_round_trip_in_gimbal_lock() {
+        // x and z turn around the same axis, so only the rotation itself survives
+        let q = Quaternion::from_euler(&Vector::new(0.4, PI / 2.0, 0.3));
+        let euler = q.to_euler();
+        assert_near(euler.y, PI / 2.0);
+        assert_near(euler.z, 0.0);
+        assert_rotation_near(&Quaternion::from_euler(&euler), &q);
+    }
+
+    #[test]
+    fn axis_angle_round_trip() {
+        let axis = Vector::new(1.0, 2.0, -2.0);
+        for angle in &[0.1, 1.0, 2.5, PI] {
+            let (result_axis, result_angle) = Quaternion::from_axis_angle(&axis, *angle).to_axis_angle();
+            assert_vector_near(&result_axis, &axis.normalize());
+            assert_near(result_angle, *angle);
+        }
+
+        let (axis, angle) = Quaternion::identity().to_axis_angle();
+        assert_vector_near(&axis, &Vector::new(1.0, 0.0, 0.0));
+        assert_near(angle, 0.0);
+    }
+
+    #[test]
+    fn axis_angle_turns_counter_clockwise() {
+        let q = Quaternion::from_axis_angle(&Vector::new(0.0, 0.0, 1.0), PI / 2.0);
+        assert_vector_near(&q.rotate(&Vector::new(1.0, 0.0, 0.0)), &Vector::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn slerp_endpoints() {
+        let from = Quaternion::from_euler(&Vector::new(0.2, 0.5, -0.3));
+        let to = Quaternion::from_euler(&Vector::new(-1.0, 2.0, 0.7));
+        assert_rotation_near(&from.slerp(&to, 0.0), &from);
+        assert_rotation_near(&from.slerp(&to, 1.0), &to);
+
+        // the short way round, even when `to` is stored with the opposite sign
+        let flipped = Quaternion::new(-to.x, -to.y, -to.z, -to.w);
+        assert_rotation_near(&from.slerp(&flipped, 1.0), &to);
+        assert_rotation_near(&from.slerp(&flipped, 0.5), &from.slerp(&to, 0.5));
+    }
+
+    #[test]
+    fn slerp_at_constant_speed() {
+        let axis = Vector::new(0.0, 1.0, 0.0);
+        let from = Quaternion::identity();
+        let to = Quaternion::from_axis_angle(&axis, 2.0);
+        assert_rotation_near(&from.slerp(&to, 0.25), &Quaternion::from_axis_angle(&axis, 0.5));
+    }
+
+    #[test]
+    fn transform_rotates_then_moves() {
+        let rotation = Quaternion::from_euler(&Vector::new(0.3, -0.7, 1.1));
+        let pos = Vector::new(4.0, -2.0, 0.5);
+        let point = Vector::new(1.0, 2.0, 3.0);
+
+        let result = transform(&pos, &rotation) * vec4(point.x, point.y, point.z, 1.0);
+        assert_vector_near(&Vector::new(result.x, result.y, result.z), &(rotation.rotate(&point) + pos));
+        assert_near(result.w, 1.0);
+    }
+
+    #[test]
+    fn euler_matrix_matches_quaternion() {
+        let euler = Vector::new(0.3, -0.7, 1.1);
+        let expected = matrix4_to_array(Quaternion::from_euler(&euler).to_matrix());
+        for (a, b) in matrix4_to_array(rotation(&euler)).iter().zip(expected.iter()) {
+            assert_near(*a, *b);
+        }
+    }
+
+    #[test]
+    fn euler_transform_matches_quaternion() {
+        let euler = Vector::new(-1.2, 0.4, 2.0);
+        let pos = Vector::new(1.0, 0.0, -3.0);
+        let expected = Transform::from_euler(pos, &euler);
+        for (a, b) in matrix4_to_array(trasform(&pos, &euler)).iter().zip(expected.matrix().iter()) {
+            assert_near(*a, *b);
+        }
+        assert_vector_near(&expected.rotation(), &euler);
+    }
+}
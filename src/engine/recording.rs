@@ -0,0 +1,137 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+use super::capture::{self, MAX_CAPTURE_SIZE};
+use super::utils;
+
+#[derive(Debug, Clone)]
+pub enum RecordingOutput {
+    // numbered PNGs in a new directory inside this one
+    ImageSequence(PathBuf),
+    // raw RGBA8 frames written to the stdin of this program. {width}, {height}, {fps}
+    // and {timestamp} in the arguments are replaced when the recording starts
+    Encoder { program: String, args: Vec<String> },
+}
+
+impl RecordingOutput {
+    // H.264 video through ffmpeg, which has to be on the PATH
+    pub fn ffmpeg() -> RecordingOutput {
+        let args = "-y -f rawvideo -pix_fmt rgba -s {width}x{height} -r {fps} -i - -c:v libx264 -pix_fmt yuv420p recording-{timestamp}.mp4";
+        RecordingOutput::Encoder {
+            program: "ffmpeg".to_string(),
+            args: args.split(' ').map(|arg| arg.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordingSettings {
+    pub width: u32,
+    pub height: u32,
+    // simulated frames per second, unrelated to how fast frames are actually rendered
+    pub fps: f32,
+    pub output: RecordingOutput,
+}
+
+impl Default for RecordingOutput {
+    // into the working directory
+    fn default() -> RecordingOutput {
+        RecordingOutput::ImageSequence(PathBuf::from("."))
+    }
+}
+
+enum FrameSink {
+    Images(PathBuf),
+    Encoder(Child),
+}
+
+// A recording in progress, every written frame advances the simulation by `frame_dt`
+pub struct Recording {
+    pub width: u32,
+    pub height: u32,
+    pub frame_dt: f32,
+    frames: u32,
+    sink: FrameSink,
+}
+
+impl Recording {
+    pub fn start(settings: &RecordingSettings) -> io::Result<Recording> {
+        if settings.fps <= 0.0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "recording frame rate must be positive"));
+        }
+        let width = settings.width.max(1).min(MAX_CAPTURE_SIZE);
+        let height = settings.height.max(1).min(MAX_CAPTURE_SIZE);
+        let timestamp = utils::timestamp();
+
+        let sink = match &settings.output {
+            RecordingOutput::ImageSequence(parent) => {
+                let directory = parent.join(format!("recording-{}", timestamp));
+                fs::create_dir_all(&directory)?;
+                FrameSink::Images(directory)
+            }
+            RecordingOutput::Encoder { program, args } => {
+                let args = args.iter().map(|arg| {
+                    arg.replace("{width}", &width.to_string())
+                        .replace("{height}", &height.to_string())
+                        .replace("{fps}", &settings.fps.to_string())
+                        .replace("{timestamp}", &timestamp)
+                });
+                let child = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .spawn()?;
+                FrameSink::Encoder(child)
+            }
+        };
+
+        Ok(Recording {
+            width: width,
+            height: height,
+            frame_dt: 1.0 / settings.fps,
+            frames: 0,
+            sink: sink,
+        })
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    // `pixels` are tightly packed RGBA8 rows of `width` by `height`
+    pub fn write_frame(&mut self, pixels: &[u8]) -> io::Result<()> {
+        match &mut self.sink {
+            FrameSink::Images(directory) => {
+                let path = directory.join(format!("frame-{:06}.png", self.frames));
+                capture::write_png(path, self.width, self.height, pixels)?;
+            }
+            FrameSink::Encoder(child) => {
+                let stdin = child.stdin.as_mut()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "encoder stdin is closed"))?;
+                stdin.write_all(pixels)?;
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    // Closes the encoder's input and waits for it to write the file
+    pub fn finish(self) -> io::Result<u32> {
+        if let FrameSink::Encoder(mut child) = self.sink {
+            drop(child.stdin.take());
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(io::Error::new(io::ErrorKind::Other, format!("encoder exited with {}", status)));
+            }
+        }
+        Ok(self.frames)
+    }
+
+    pub fn location(&self) -> Option<&Path> {
+        match &self.sink {
+            FrameSink::Images(directory) => Some(directory),
+            FrameSink::Encoder(_) => None,
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use super::actions::Action;
+use super::animation::Pose;
+use super::camera::Projection;
+use super::quaternion::Quaternion;
+use super::types::Vector;
+
+// Ticks between the camera poses stored to check a replay against, one second at 120 Hz
+const CHECKPOINT_TICKS: u64 = 120;
+const FORMAT_VERSION: u32 = 1;
+
+// Everything that reaches the simulation from outside, applied before the tick it's recorded for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    Down(Action),
+    Up(Action),
+    // pixels
+    MouseMotion(f32, f32),
+    // lines
+    Scroll(f32),
+    // rotation mouse look handed to the camera this tick, stored as is because it's smoothed
+    Turn(f32, f32),
+    ReleaseAll,
+}
+
+// Camera settings and controller a recording starts from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StartState {
+    pub pose: Pose,
+    pub projection: Projection,
+    pub view_height: f32,
+    pub fov: f32,
+    pub aspect_ratio: f32,
+    pub speed: f32,
+    pub rotation_speed: f32,
+    pub controller: usize,
+    // window size, which the aspect ratio and framing depend on
+    pub size: (u32, u32),
+}
+
+// Input events by fixed tick index. Replaying them from the same start at the same tick length
+// runs the exact same float operations, so the camera follows the same path bit for bit.
+#[derive(Debug, Clone)]
+pub struct InputRecording {
+    pub tick_dt: f32,
+    pub start: StartState,
+    pub ticks: u64,
+    events: Vec<(u64, InputEvent)>,
+    // camera pose after every CHECKPOINT_TICKS ticks and after the last one
+    checkpoints: Vec<(u64, Pose)>,
+    last_pose: Option<Pose>,
+}
+
+impl InputRecording {
+    pub fn new(tick_dt: f32, start: StartState) -> InputRecording {
+        InputRecording {
+            tick_dt: tick_dt,
+            start: start,
+            ticks: 0,
+            events: Vec::new(),
+            checkpoints: Vec::new(),
+            last_pose: None,
+        }
+    }
+
+    // Records an event for the next tick
+    pub fn record(&mut self, event: InputEvent) {
+        // releasing twice in a row changes nothing
+        if event == InputEvent::ReleaseAll && self.events.last() == Some(&(self.ticks, InputEvent::ReleaseAll)) {
+            return;
+        }
+        self.events.push((self.ticks, event));
+    }
+
+    pub fn end_tick(&mut self, pose: &Pose) {
+        self.ticks += 1;
+        if self.ticks % CHECKPOINT_TICKS == 0 {
+            self.checkpoints.push((self.ticks, *pose));
+        }
+        self.last_pose = Some(*pose);
+    }
+
+    pub fn events(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let start = &self.start;
+        writeln!(out, "version {}", FORMAT_VERSION)?;
+        writeln!(out, "tick_dt {}", self.tick_dt)?;
+        writeln!(out, "ticks {}", self.ticks)?;
+        writeln!(out, "size {} {}", start.size.0, start.size.1)?;
+        writeln!(out, "pose {}", pose_to_string(&start.pose))?;
+        writeln!(out, "projection {}", match start.projection {
+            Projection::Perspective => "perspective".to_string(),
+            Projection::Orthographic => "orthographic".to_string(),
+            Projection::Oblique { angle, depth_scale } => format!("oblique {} {}", angle, depth_scale),
+        })?;
+        writeln!(out, "view_height {}", start.view_height)?;
+        writeln!(out, "fov {}", start.fov)?;
+        writeln!(out, "aspect_ratio {}", start.aspect_ratio)?;
+        writeln!(out, "speed {}", start.speed)?;
+        writeln!(out, "rotation_speed {}", start.rotation_speed)?;
+        writeln!(out, "controller {}", start.controller)?;
+
+        let mut checkpoints = self.checkpoints.clone();
+        if let Some(pose) = self.last_pose {
+            if self.ticks % CHECKPOINT_TICKS != 0 {
+                checkpoints.push((self.ticks, pose));
+            }
+        }
+
+        // a checkpoint is the pose once that many ticks have run, before the events of the next one
+        let mut checkpoints = checkpoints.iter().peekable();
+        for (tick, event) in &self.events {
+            while let Some((checkpoint_tick, pose)) = checkpoints.peek() {
+                if checkpoint_tick > tick {
+                    break;
+                }
+                writeln!(out, "{} checkpoint {}", checkpoint_tick, pose_to_string(pose))?;
+                checkpoints.next();
+            }
+            match event {
+                InputEvent::Down(action) => writeln!(out, "{} down {}", tick, action.name())?,
+                InputEvent::Up(action) => writeln!(out, "{} up {}", tick, action.name())?,
+                InputEvent::MouseMotion(dx, dy) => writeln!(out, "{} motion {} {}", tick, dx, dy)?,
+                InputEvent::Scroll(lines) => writeln!(out, "{} scroll {}", tick, lines)?,
+                InputEvent::Turn(yaw, pitch) => writeln!(out, "{} turn {} {}", tick, yaw, pitch)?,
+                InputEvent::ReleaseAll => writeln!(out, "{} release_all", tick)?,
+            }
+        }
+        for (tick, pose) in checkpoints {
+            writeln!(out, "{} checkpoint {}", tick, pose_to_string(pose))?;
+        }
+        out.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<InputRecording> {
+        let text = fs::read_to_string(path)?;
+        let mut recording = InputRecording::new(0.0, StartState {
+            pose: Pose::from_yaw_pitch(Vector::new(0.0, 0.0, 0.0), 0.0, 0.0),
+            projection: Projection::Perspective,
+            view_height: 0.0,
+            fov: 0.0,
+            aspect_ratio: 1.0,
+            speed: 0.0,
+            rotation_speed: 0.0,
+            controller: 0,
+            size: (0, 0),
+        });
+
+        for (index, line) in text.lines().enumerate() {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.is_empty() {
+                continue;
+            }
+            parse_line(&mut recording, &fields)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid line {}: {}", index + 1, line)))?;
+        }
+        Ok(recording)
+    }
+}
+
+fn pose_to_string(pose: &Pose) -> String {
+    let (p, q) = (&pose.position, &pose.orientation);
+    format!("{} {} {} {} {} {} {}", p.x, p.y, p.z, q.x, q.y, q.z, q.w)
+}
+
+fn parse_pose(fields: &[&str]) -> Option<Pose> {
+    let values = parse_floats(fields)?;
+    if values.len() != 7 {
+        return None;
+    }
+    Some(Pose {
+        position: Vector::new(values[0], values[1], values[2]),
+        orientation: Quaternion::new(values[3], values[4], values[5], values[6]),
+    })
+}
+
+fn parse_floats(fields: &[&str]) -> Option<Vec<f32>> {
+    fields.iter().map(|field| field.parse().ok()).collect()
+}
+
+fn parse_line(recording: &mut InputRecording, fields: &[&str]) -> Option<()> {
+    let float = |index: usize| fields.get(index).and_then(|field| field.parse::<f32>().ok());
+
+    if let Ok(tick) = fields[0].parse::<u64>() {
+        let event = match *fields.get(1)? {
+            "down" => InputEvent::Down(Action::parse(fields.get(2)?)?),
+            "up" => InputEvent::Up(Action::parse(fields.get(2)?)?),
+            "motion" => InputEvent::MouseMotion(float(2)?, float(3)?),
+            "scroll" => InputEvent::Scroll(float(2)?),
+            "turn" => InputEvent::Turn(float(2)?, float(3)?),
+            "release_all" => InputEvent::ReleaseAll,
+            "checkpoint" => {
+                recording.checkpoints.push((tick, parse_pose(&fields[2..])?));
+                return Some(());
+            }
+            _ => return None,
+        };
+        recording.events.push((tick, event));
+        return Some(());
+    }
+
+    let start = &mut recording.start;
+    match fields[0] {
+        "version" => {
+            if fields.get(1)?.parse::<u32>().ok()? != FORMAT_VERSION {
+                return None;
+            }
+        }
+        "tick_dt" => recording.tick_dt = float(1)?,
+        "ticks" => recording.ticks = fields.get(1)?.parse().ok()?,
+        "size" => start.size = (fields.get(1)?.parse().ok()?, fields.get(2)?.parse().ok()?),
+        "pose" => start.pose = parse_pose(&fields[1..])?,
+        "projection" => {
+            start.projection = match *fields.get(1)? {
+                "perspective" => Projection::Perspective,
+                "orthographic" => Projection::Orthographic,
+                "oblique" => Projection::Oblique { angle: float(2)?, depth_scale: float(3)? },
+                _ => return None,
+            }
+        }
+        "view_height" => start.view_height = float(1)?,
+        "fov" => start.fov = float(1)?,
+        "aspect_ratio" => start.aspect_ratio = float(1)?,
+        "speed" => start.speed = float(1)?,
+        "rotation_speed" => start.rotation_speed = float(1)?,
+        "controller" => start.controller = fields.get(1)?.parse().ok()?,
+        _ => return None,
+    }
+    Some(())
+}
+
+// How a replay went, a divergence means something outside the recorded input moved the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outcome {
+    pub ticks: u64,
+    // first checkpoint where the camera wasn't where it was when recording
+    pub diverged_at: Option<u64>,
+}
+
+// Feeds a recording back tick by tick
+pub struct Replay {
+    recording: InputRecording,
+    tick: u64,
+    next_event: usize,
+    next_checkpoint: usize,
+    diverged_at: Option<u64>,
+}
+
+impl Replay {
+    pub fn new(recording: InputRecording) -> Replay {
+        Replay {
+            recording: recording,
+            tick: 0,
+            next_event: 0,
+            next_checkpoint: 0,
+            diverged_at: None,
+        }
+    }
+
+    pub fn recording(&self) -> &InputRecording {
+        &self.recording
+    }
+
+    // Events to apply before running the current tick
+    pub fn take_events(&mut self) -> Vec<InputEvent> {
+        let events = &self.recording.events[self.next_event..];
+        let count = events.iter().take_while(|(tick, _)| *tick <= self.tick).count();
+        self.next_event += count;
+        events[..count].iter().map(|(_, event)| *event).collect()
+    }
+
+    // Checks the camera against the recording and moves on to the next tick
+    pub fn end_tick(&mut self, pose: &Pose) {
+        self.tick += 1;
+        let checkpoints = &self.recording.checkpoints;
+        while self.next_checkpoint < checkpoints.len() && checkpoints[self.next_checkpoint].0 <= self.tick {
+            let (tick, recorded) = checkpoints[self.next_checkpoint];
+            if tick == self.tick && recorded != *pose && self.diverged_at.is_none() {
+                self.diverged_at = Some(tick);
+            }
+            self.next_checkpoint += 1;
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.recording.ticks
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        Outcome {
+            ticks: self.tick,
+            diverged_at: self.diverged_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_state() -> StartState {
+        StartState {
+            pose: Pose::from_yaw_pitch(Vector::new(1.0, 2.0, 3.0), 0.5, -0.25),
+            projection: Projection::Oblique { angle: 0.75, depth_scale: 0.5 },
+            view_height: 4.0,
+            fov: 1.2,
+            aspect_ratio: 1.5,
+            speed: 3.0,
+            rotation_speed: 2.0,
+            controller: 1,
+            size: (1280, 720),
+        }
+    }
+
+    fn pose_at(tick: u64) -> Pose {
+        Pose::from_yaw_pitch(Vector::new(tick as f32 * 0.1, 0.0, 0.0), tick as f32 * 0.01, 0.0)
+    }
+
+    // Down on tick 0, motion on tick 5, scroll and turn on tick 125, 130 ticks in all
+    fn recording() -> InputRecording {
+        let mut recording = InputRecording::new(1.0 / 120.0, start_state());
+        for tick in 0..130 {
+            match tick {
+                0 => {
+                    recording.record(InputEvent::Down(Action::MoveForward));
+                    recording.record(InputEvent::ReleaseAll);
+                    recording.record(InputEvent::ReleaseAll);
+                }
+                5 => recording.record(InputEvent::MouseMotion(-3.5, 0.125)),
+                125 => {
+                    recording.record(InputEvent::Up(Action::MoveForward));
+                    recording.record(InputEvent::Scroll(-1.0));
+                    recording.record(InputEvent::Turn(0.01, -0.02));
+                }
+                _ => {}
+            }
+            recording.end_tick(&pose_at(tick + 1));
+        }
+        recording
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let recording = recording();
+        let path = std::env::temp_dir().join(format!("replay-test-{}.txt", std::process::id()));
+        recording.save(&path).unwrap();
+        let loaded = InputRecording::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.tick_dt, recording.tick_dt);
+        assert_eq!(loaded.ticks, 130);
+        assert_eq!(loaded.start, recording.start);
+        // the second release in a row isn't recorded
+        assert_eq!(loaded.events, recording.events);
+        assert_eq!(loaded.events(), 6);
+        // the last pose is saved as a checkpoint of its own
+        assert_eq!(loaded.checkpoints, vec![(120, pose_at(120)), (130, pose_at(130))]);
+    }
+
+    #[test]
+    fn load_rejects_invalid_lines() {
+        let path = std::env::temp_dir().join(format!("replay-test-invalid-{}.txt", std::process::id()));
+        fs::write(&path, "version 1\n3 jump\n").unwrap();
+        let loaded = InputRecording::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn replay_hands_out_events_by_tick() {
+        let mut replay = Replay::new(recording());
+        let mut events = Vec::new();
+        while !replay.is_finished() {
+            for event in replay.take_events() {
+                events.push((replay.tick, event));
+            }
+            let pose = pose_at(replay.tick + 1);
+            replay.end_tick(&pose);
+        }
+
+        assert_eq!(events, replay.recording().events);
+        assert_eq!(replay.outcome(), Outcome { ticks: 130, diverged_at: None });
+    }
+
+    #[test]
+    fn replay_reports_the_first_divergence() {
+        let mut replay = Replay::new(recording());
+        while !replay.is_finished() {
+            replay.take_events();
+            // off by a little from tick 100 on
+            let mut pose = pose_at(replay.tick + 1);
+            if replay.tick >= 100 {
+                pose.position.y += 0.001;
+            }
+            replay.end_tick(&pose);
+        }
+
+        assert_eq!(replay.outcome(), Outcome { ticks: 130, diverged_at: Some(120) });
+    }
+}
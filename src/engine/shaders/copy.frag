@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) in vec2 v_Uv;
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_Source;
+layout(set = 0, binding = 1) uniform sampler s_Source;
+
+// One texel per pixel, the source has the size of the output
+void main() {
+    outColor = texture(sampler2D(t_Source, s_Source), v_Uv);
+}
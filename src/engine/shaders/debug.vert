@@ -0,0 +1,20 @@
+#version 450
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+
+layout(location = 0) out vec3 fragColor;
+
+layout(set = 0, binding = 0) uniform Locals {
+    mat4 u_Transform;
+    mat4 u_ViewProjection;
+    mat4 u_PrevViewProjection;
+    vec4 u_Jitter;
+    vec4 u_Params;
+};
+
+// Debug lines are drawn after the TAA resolve so they use the unjittered matrix
+void main() {
+    gl_Position = u_ViewProjection * vec4(inPosition, 1.0);
+    fragColor = inColor;
+}
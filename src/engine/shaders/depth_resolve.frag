@@ -0,0 +1,11 @@
+#version 450
+
+layout(location = 0) in vec2 v_Uv;
+
+layout(set = 0, binding = 0) uniform texture2DMS t_Depth;
+layout(set = 0, binding = 1) uniform sampler s_Depth;
+
+// The first sample stands in for the pixel, overlays may be off by one sample at silhouettes
+void main() {
+    gl_FragDepth = texelFetch(sampler2DMS(t_Depth, s_Depth), ivec2(gl_FragCoord.xy), 0).r;
+}
@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) out vec2 v_Uv;
+
+// Single triangle covering the screen, no vertex buffer needed
+void main() {
+    vec2 pos = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+    v_Uv = vec2(pos.x, 1.0 - pos.y);
+}
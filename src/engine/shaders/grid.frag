@@ -0,0 +1,90 @@
+#version 450
+
+layout(location = 0) in vec2 v_Uv;
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform GridLocals {
+    mat4 u_ViewProjection;
+    mat4 u_InverseViewProjection;
+    vec4 u_Eye;
+    // x: minor spacing at height 1, y: fade distance at height 1, z: minor lines per major line,
+    // w: 1 for reversed depth
+    vec4 u_Params;
+};
+
+vec3 unproject(vec2 ndc, float depth) {
+    vec4 p = u_InverseViewProjection * vec4(ndc, depth, 1.0);
+    return p.xyz / p.w;
+}
+
+// 1.0 on a line of the given spacing, anti-aliased to one pixel with screen space derivatives
+float lines(vec2 coord, float spacing) {
+    vec2 c = coord / spacing;
+    vec2 width = fwidth(c);
+    vec2 g = abs(fract(c - 0.5) - 0.5) / width;
+    return 1.0 - min(min(g.x, g.y), 1.0);
+}
+
+void main() {
+    vec2 ndc = vec2(v_Uv.x * 2.0 - 1.0, 1.0 - v_Uv.y * 2.0);
+    // reversed depth can put the far plane at infinity, so stop halfway there
+    bool reversed = u_Params.w > 0.5;
+    vec3 near = unproject(ndc, reversed ? 1.0 : 0.0);
+    vec3 far = unproject(ndc, reversed ? 0.5 : 1.0);
+
+    // Intersect the view ray with the y = 0 plane
+    float dy = far.y - near.y;
+    if (abs(dy) < 1e-6) {
+        discard;
+    }
+    float t = -near.y / dy;
+    if (t <= 0.0) {
+        discard;
+    }
+    vec3 p = near + t * (far - near);
+
+    vec4 clip = u_ViewProjection * vec4(p, 1.0);
+    float depth = clip.z / clip.w;
+    if (depth < 0.0 || depth > 1.0) {
+        discard;
+    }
+    gl_FragDepth = depth;
+
+    // Spacing grows by a decade as the camera rises, cross fading between levels
+    float height = max(abs(u_Eye.y), 1.0);
+    float lod = log(height) / log(10.0);
+    float level = floor(lod);
+    float blend = fract(lod);
+    float minorSpacing = u_Params.x * pow(10.0, level);
+    float majorSpacing = minorSpacing * u_Params.z;
+
+    float minor = lines(p.xz, minorSpacing) * (1.0 - blend);
+    float major = lines(p.xz, majorSpacing);
+
+    vec3 color = vec3(0.35);
+    float alpha = max(minor * 0.35, major * 0.7);
+    if (major > minor * 0.35) {
+        color = vec3(0.6);
+    }
+
+    // X axis runs along z = 0, Z axis along x = 0
+    vec2 axisWidth = fwidth(p.xz);
+    if (abs(p.z) < axisWidth.y) {
+        color = vec3(0.9, 0.2, 0.2);
+        alpha = 1.0;
+    }
+    if (abs(p.x) < axisWidth.x) {
+        color = vec3(0.2, 0.3, 0.9);
+        alpha = 1.0;
+    }
+
+    float fadeDistance = u_Params.y * height;
+    float distance = length(p.xz - u_Eye.xz);
+    alpha *= 1.0 - smoothstep(fadeDistance * 0.5, fadeDistance, distance);
+
+    if (alpha <= 0.0) {
+        discard;
+    }
+    outColor = vec4(color, alpha);
+}
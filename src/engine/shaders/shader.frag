@@ -1,10 +1,67 @@
+// permutations: VIS_WIREFRAME VIS_NORMALS VIS_UVS VIS_DEPTH VIS_OVERDRAW VIS_MESH_COLOR VIS_LIGHT_COMPLEXITY
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
 
 layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec4 currClip;
+layout(location = 2) in vec4 prevClip;
+layout(location = 3) in vec3 fragNormal;
+layout(location = 4) in vec2 fragUv;
 
 layout(location = 0) out vec4 outColor;
+layout(location = 1) out vec2 outVelocity;
+
+layout(set = 0, binding = 0) uniform Locals {
+    mat4 u_Transform;
+    mat4 u_ViewProjection;
+    mat4 u_PrevViewProjection;
+    vec4 u_Jitter;
+    // x: near, y: far, z: mesh id, w: light count
+    vec4 u_Params;
+    // x: 1 for reverse-Z with an infinite far plane
+    vec4 u_Depth;
+};
+
+vec3 hashColor(float id) {
+    vec3 p = fract(vec3(id) * vec3(0.1031, 0.1030, 0.0973) + 0.5);
+    p += dot(p, p.yxz + 33.33);
+    return fract((p.xxy + p.yzz) * p.zyx);
+}
+
+// Green for no lights through yellow to red at 8 or more
+vec3 heat(float t) {
+    t = clamp(t, 0.0, 1.0);
+    return mix(mix(vec3(0.0, 0.8, 0.0), vec3(1.0, 1.0, 0.0), min(t * 2.0, 1.0)), vec3(1.0, 0.0, 0.0), max(t * 2.0 - 1.0, 0.0));
+}
 
 void main() {
+#if defined(VIS_WIREFRAME)
+    outColor = vec4(1.0, 1.0, 1.0, 1.0);
+#elif defined(VIS_NORMALS)
+    outColor = vec4(normalize(fragNormal) * 0.5 + 0.5, 1.0);
+#elif defined(VIS_UVS)
+    outColor = vec4(fract(fragUv), 0.0, 1.0);
+#elif defined(VIS_DEPTH)
+    float near = u_Params.x;
+    float far = u_Params.y;
+    // with an infinite far plane, far only sets the range that is shaded
+    float linear = u_Depth.x > 0.5
+        ? near / max(gl_FragCoord.z, 1e-7)
+        : near * far / (far - gl_FragCoord.z * (far - near));
+    float shade = log(linear / near) / log(far / near);
+    outColor = vec4(vec3(1.0 - shade), 1.0);
+#elif defined(VIS_OVERDRAW)
+    // accumulated additively, every layer adds a little heat
+    outColor = vec4(0.1, 0.04, 0.01, 1.0);
+#elif defined(VIS_MESH_COLOR)
+    outColor = vec4(hashColor(u_Params.z + 1.0), 1.0);
+#elif defined(VIS_LIGHT_COMPLEXITY)
+    outColor = vec4(heat(u_Params.w / 8.0), 1.0);
+#else
     outColor = vec4(fragColor, 1.0);
-}
\ No newline at end of file
+#endif
+
+    // Screen space motion in uv units, texture v runs opposite to clip y
+    vec2 delta = (currClip.xy / currClip.w) - (prevClip.xy / prevClip.w);
+    outVelocity = vec2(delta.x, -delta.y) * 0.5;
+}
@@ -2,15 +2,35 @@
 
 layout(location = 0) in vec3 inPosition;
 layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec3 inNormal;
+layout(location = 3) in vec2 inUv;
 
 layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec4 currClip;
+layout(location = 2) out vec4 prevClip;
+layout(location = 3) out vec3 fragNormal;
+layout(location = 4) out vec2 fragUv;
 
 layout(set = 0, binding = 0) uniform Locals {
     mat4 u_Transform;
+    mat4 u_ViewProjection;
+    mat4 u_PrevViewProjection;
+    vec4 u_Jitter;
+    vec4 u_Params;
+    vec4 u_Depth;
+    // object to world, this frame and the last
+    mat4 u_Model;
+    mat4 u_PrevModel;
 };
 
 
 void main() {
-    gl_Position = u_Transform * vec4(inPosition, 1.0);
+    vec4 position = u_Model * vec4(inPosition, 1.0);
+    gl_Position = u_Transform * position;
+    currClip = u_ViewProjection * position;
+    prevClip = u_PrevViewProjection * u_PrevModel * vec4(inPosition, 1.0);
     fragColor = inColor;
-}
\ No newline at end of file
+    // the model matrix only rotates and moves, so it turns normals as it is
+    fragNormal = mat3(u_Model) * inNormal;
+    fragUv = inUv;
+}
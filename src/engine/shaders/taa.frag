@@ -0,0 +1,57 @@
+#version 450
+
+layout(location = 0) in vec2 v_Uv;
+
+layout(location = 0) out vec4 outColor;
+layout(location = 1) out vec4 outHistory;
+
+layout(set = 0, binding = 0) uniform texture2D t_Current;
+layout(set = 0, binding = 1) uniform texture2D t_Velocity;
+layout(set = 0, binding = 2) uniform texture2D t_History;
+layout(set = 0, binding = 3) uniform sampler s_Point;
+layout(set = 0, binding = 4) uniform sampler s_Linear;
+layout(set = 0, binding = 5) uniform TaaLocals {
+    vec2 u_TexelSize;
+    float u_Blend;
+    float u_HistoryValid;
+};
+
+void main() {
+    vec3 current = texture(sampler2D(t_Current, s_Point), v_Uv).rgb;
+
+    // Neighbourhood bounds for clamping and the longest velocity around the pixel,
+    // so silhouettes of moving thin geometry reproject with the foreground motion
+    vec3 neighbourMin = current;
+    vec3 neighbourMax = current;
+    vec2 velocity = vec2(0.0);
+    float longest = -1.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            vec2 uv = v_Uv + vec2(x, y) * u_TexelSize;
+            vec3 neighbour = texture(sampler2D(t_Current, s_Point), uv).rgb;
+            neighbourMin = min(neighbourMin, neighbour);
+            neighbourMax = max(neighbourMax, neighbour);
+
+            vec2 v = texture(sampler2D(t_Velocity, s_Point), uv).xy;
+            float len = dot(v, v);
+            if (len > longest) {
+                longest = len;
+                velocity = v;
+            }
+        }
+    }
+
+    vec2 historyUv = v_Uv - velocity;
+    vec3 history = texture(sampler2D(t_History, s_Linear), historyUv).rgb;
+    history = clamp(history, neighbourMin, neighbourMax);
+
+    float blend = u_Blend;
+    bool offscreen = any(lessThan(historyUv, vec2(0.0))) || any(greaterThan(historyUv, vec2(1.0)));
+    if (u_HistoryValid < 0.5 || offscreen) {
+        blend = 1.0;
+    }
+
+    vec3 result = mix(history, current, blend);
+    outColor = vec4(result, 1.0);
+    outHistory = vec4(result, 1.0);
+}
@@ -0,0 +1,14 @@
+#version 450
+
+layout(location = 0) in vec2 fragUv;
+layout(location = 1) in vec4 fragColor;
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_Atlas;
+layout(set = 0, binding = 1) uniform sampler s_Atlas;
+
+void main() {
+    float coverage = texture(sampler2D(t_Atlas, s_Atlas), fragUv).r;
+    outColor = vec4(fragColor.rgb, fragColor.a * coverage);
+}
@@ -0,0 +1,20 @@
+#version 450
+
+layout(location = 0) in vec2 inPosition;
+layout(location = 1) in vec2 inUv;
+layout(location = 2) in vec4 inColor;
+
+layout(location = 0) out vec2 fragUv;
+layout(location = 1) out vec4 fragColor;
+
+layout(set = 0, binding = 2) uniform TextLocals {
+    vec4 u_ScreenSize;
+};
+
+// Positions are in pixels from the top left corner of the window
+void main() {
+    vec2 ndc = inPosition / u_ScreenSize.xy * 2.0 - 1.0;
+    gl_Position = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+    fragUv = inUv;
+    fragColor = inColor;
+}
@@ -0,0 +1,288 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use super::text::TextRenderer;
+
+const HISTORY_LEN: usize = 240;
+
+// HUD
+const OVERLAY_WIDTH: f32 = 240.0;
+const GRAPH_HEIGHT: f32 = 60.0;
+// frame time at the top of the graph, two frames at 60Hz
+const GRAPH_MAX_MS: f32 = 33.3;
+const OVERLAY_TEXT_SIZE: f32 = 14.0;
+
+// Live GPU objects, counted where they're created and dropped
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResourceCounts {
+    pub buffers: u32,
+    pub textures: u32,
+    pub samplers: u32,
+    pub bind_groups: u32,
+    pub pipelines: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ResourceKind {
+    Buffer,
+    Texture,
+    Sampler,
+    BindGroup,
+    Pipeline,
+}
+
+// Indexed by ResourceKind
+static LIVE: [AtomicU32; 5] = [
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+];
+
+pub fn live_resources() -> ResourceCounts {
+    let count = |kind: ResourceKind| LIVE[kind as usize].load(Ordering::Relaxed);
+    ResourceCounts {
+        buffers: count(ResourceKind::Buffer),
+        textures: count(ResourceKind::Texture),
+        samplers: count(ResourceKind::Sampler),
+        bind_groups: count(ResourceKind::BindGroup),
+        pipelines: count(ResourceKind::Pipeline),
+    }
+}
+
+fn add_live(kind: ResourceKind, count: u32) {
+    LIVE[kind as usize].fetch_add(count, Ordering::Relaxed);
+}
+
+fn remove_live(kind: ResourceKind, count: u32) {
+    LIVE[kind as usize].fetch_sub(count, Ordering::Relaxed);
+}
+
+pub trait Resource {
+    const KIND: ResourceKind;
+}
+
+impl Resource for wgpu::Buffer {
+    const KIND: ResourceKind = ResourceKind::Buffer;
+}
+
+impl Resource for wgpu::Texture {
+    const KIND: ResourceKind = ResourceKind::Texture;
+}
+
+impl Resource for wgpu::Sampler {
+    const KIND: ResourceKind = ResourceKind::Sampler;
+}
+
+impl Resource for wgpu::BindGroup {
+    const KIND: ResourceKind = ResourceKind::BindGroup;
+}
+
+impl Resource for wgpu::RenderPipeline {
+    const KIND: ResourceKind = ResourceKind::Pipeline;
+}
+
+// A GPU object that counts itself as live until it's dropped
+pub struct Tracked<T: Resource>(T);
+
+impl<T: Resource> Tracked<T> {
+    pub fn new(resource: T) -> Tracked<T> {
+        add_live(T::KIND, 1);
+        Tracked(resource)
+    }
+}
+
+impl<T: Resource> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Resource> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        remove_live(T::KIND, 1);
+    }
+}
+
+// Everything measured for one rendered frame, times are in milliseconds
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    pub frame: u64,
+    pub frame_ms: f32,
+    pub update_ms: f32,
+    pub encode_ms: f32,
+    pub submit_ms: f32,
+    pub draw_calls: u32,
+    pub triangles: u32,
+    pub buffer_uploads: u32,
+    pub upload_bytes: u64,
+}
+
+impl FrameStats {
+    pub fn draw(&mut self, triangles: u32) {
+        self.draw_calls += 1;
+        self.triangles += triangles;
+    }
+
+    pub fn upload(&mut self, bytes: usize) {
+        self.buffer_uploads += 1;
+        self.upload_bytes += bytes as u64;
+    }
+
+    pub fn merge(&mut self, other: &FrameStats) {
+        self.draw_calls += other.draw_calls;
+        self.triangles += other.triangles;
+        self.buffer_uploads += other.buffer_uploads;
+        self.upload_bytes += other.upload_bytes;
+    }
+}
+
+pub struct Stats {
+    pub overlay: bool,
+    current: FrameStats,
+    history: VecDeque<FrameStats>,
+    last_frame: Option<Instant>,
+    frame_count: u64,
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            overlay: false,
+            current: FrameStats::default(),
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            last_frame: None,
+            frame_count: 0,
+        }
+    }
+
+    // The frame being recorded, subsystems add their draws and uploads to it
+    pub fn current(&mut self) -> &mut FrameStats {
+        &mut self.current
+    }
+
+    pub fn add_update_time(&mut self, duration: Duration) {
+        self.current.update_ms += to_ms(duration);
+    }
+
+    pub fn end_frame(&mut self, encode: Duration, submit: Duration) {
+        let now = Instant::now();
+        self.current.frame = self.frame_count;
+        self.current.frame_ms = self.last_frame.map(|last| to_ms(now.duration_since(last))).unwrap_or(0.0);
+        self.current.encode_ms = to_ms(encode);
+        self.current.submit_ms = to_ms(submit);
+
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.current);
+
+        self.current = FrameStats::default();
+        self.last_frame = Some(now);
+        self.frame_count += 1;
+    }
+
+    pub fn last(&self) -> FrameStats {
+        self.history.back().cloned().unwrap_or_default()
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &FrameStats> {
+        self.history.iter()
+    }
+
+    pub fn average_frame_ms(&self) -> f32 {
+        if self.history.is_empty() {
+            return 0.0;
+        }
+        self.history.iter().map(|frame| frame.frame_ms).sum::<f32>() / self.history.len() as f32
+    }
+
+    pub fn max_frame_ms(&self) -> f32 {
+        self.history.iter().map(|frame| frame.frame_ms).fold(0.0, f32::max)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("frame,frame_ms,update_ms,encode_ms,submit_ms,draw_calls,triangles,buffer_uploads,upload_bytes\n");
+        for frame in self.history.iter() {
+            csv.push_str(&format!(
+                "{},{:.4},{:.4},{:.4},{:.4},{},{},{},{}\n",
+                frame.frame, frame.frame_ms, frame.update_ms, frame.encode_ms, frame.submit_ms,
+                frame.draw_calls, frame.triangles, frame.buffer_uploads, frame.upload_bytes,
+            ));
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        let frames: Vec<String> = self.history.iter().map(|frame| format!(
+            "    {{\"frame\": {}, \"frame_ms\": {:.4}, \"update_ms\": {:.4}, \"encode_ms\": {:.4}, \"submit_ms\": {:.4}, \"draw_calls\": {}, \"triangles\": {}, \"buffer_uploads\": {}, \"upload_bytes\": {}}}",
+            frame.frame, frame.frame_ms, frame.update_ms, frame.encode_ms, frame.submit_ms,
+            frame.draw_calls, frame.triangles, frame.buffer_uploads, frame.upload_bytes,
+        )).collect();
+
+        let resources = live_resources();
+        format!(
+            "{{\n  \"resources\": {{\"buffers\": {}, \"textures\": {}, \"samplers\": {}, \"bind_groups\": {}, \"pipelines\": {}}},\n  \"average_frame_ms\": {:.4},\n  \"frames\": [\n{}\n  ]\n}}\n",
+            resources.buffers, resources.textures, resources.samplers, resources.bind_groups, resources.pipelines,
+            self.average_frame_ms(),
+            frames.join(",\n"),
+        )
+    }
+
+    // Writes CSV or JSON depending on the file extension
+    pub fn export<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let contents = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => self.to_json(),
+            _ => self.to_csv(),
+        };
+        fs::write(path, contents)
+    }
+}
+
+impl Stats {
+    // Frame time graph and the latest counters in the top right corner
+    pub fn draw_overlay(&self, text: &mut TextRenderer, screen_width: u32) {
+        let last = self.last();
+        let resources = live_resources();
+        let lines = [
+            format!("frame {:.2} ms (avg {:.2}, max {:.2})", last.frame_ms, self.average_frame_ms(), self.max_frame_ms()),
+            format!("update {:.2} encode {:.2} submit {:.2}", last.update_ms, last.encode_ms, last.submit_ms),
+            format!("draws {} triangles {}", last.draw_calls, last.triangles),
+            format!("uploads {} ({} bytes)", last.buffer_uploads, last.upload_bytes),
+            format!(
+                "buffers {} textures {} pipelines {}",
+                resources.buffers, resources.textures, resources.pipelines,
+            ),
+        ];
+
+        let x = screen_width as f32 - OVERLAY_WIDTH - 10.0;
+        let y = 10.0;
+        let line_height = OVERLAY_TEXT_SIZE + 4.0;
+        let height = GRAPH_HEIGHT + lines.len() as f32 * line_height + 12.0;
+        text.rect(x - 4.0, y - 4.0, OVERLAY_WIDTH + 8.0, height, [0.0, 0.0, 0.0, 0.6]);
+
+        let bar_width = OVERLAY_WIDTH / HISTORY_LEN as f32;
+        for (i, frame) in self.history.iter().enumerate() {
+            let bar_height = (frame.frame_ms / GRAPH_MAX_MS).min(1.0) * GRAPH_HEIGHT;
+            let color = if frame.frame_ms > 16.7 { [1.0, 0.3, 0.2, 0.9] } else { [0.3, 0.9, 0.3, 0.9] };
+            text.rect(x + i as f32 * bar_width, y + GRAPH_HEIGHT - bar_height, bar_width, bar_height, color);
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            text.draw(line, x, y + GRAPH_HEIGHT + 8.0 + i as f32 * line_height, OVERLAY_TEXT_SIZE, [1.0, 1.0, 1.0, 1.0]);
+        }
+    }
+}
+
+fn to_ms(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
@@ -0,0 +1,55 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::text::TextRenderer;
+
+// How long a message stays on screen
+const MESSAGE_TIME: Duration = Duration::from_secs(5);
+const MAX_MESSAGES: usize = 6;
+const TEXT_SIZE: f32 = 14.0;
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+// What the engine just did or failed to do, shown in the bottom left corner for a few seconds
+pub struct StatusLog {
+    messages: VecDeque<(String, Instant)>,
+}
+
+impl StatusLog {
+    pub fn new() -> StatusLog {
+        StatusLog {
+            messages: VecDeque::with_capacity(MAX_MESSAGES),
+        }
+    }
+
+    pub fn push<S: Into<String>>(&mut self, message: S) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((message.into(), Instant::now()));
+    }
+
+    // When the oldest message on screen is due to go
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.messages.front().map(|(_, shown)| *shown + MESSAGE_TIME)
+    }
+
+    // A message is still drawn past its time, the next frame takes it off
+    pub fn has_expired(&self) -> bool {
+        self.next_expiry().map_or(false, |expiry| expiry <= Instant::now())
+    }
+
+    // Newest at the bottom
+    pub fn draw(&mut self, text: &mut TextRenderer, screen_height: u32) {
+        let now = Instant::now();
+        while self.next_expiry().map_or(false, |expiry| expiry <= now) {
+            self.messages.pop_front();
+        }
+
+        let line_height = TEXT_SIZE + 4.0;
+        let mut y = screen_height as f32 - 10.0 - self.messages.len() as f32 * line_height;
+        for (message, _) in &self.messages {
+            text.draw(message, 10.0, y, TEXT_SIZE, TEXT_COLOR);
+            y += line_height;
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use zerocopy::{AsBytes, FromBytes};
+
+use super::stats::{FrameStats, Tracked};
+use super::targets::{RenderTargets, create_texture};
+
+// Weight of the current frame when blending with the reprojected history
+const TAA_BLEND: f32 = 0.1;
+
+#[repr(C)]
+#[derive(Clone, Copy, AsBytes, FromBytes)]
+struct TaaUniforms {
+    texel_size: [f32; 2],
+    blend: f32,
+    history_valid: f32,
+}
+
+// Temporal anti-aliasing resolve, accumulates jittered frames into a ping-ponged history
+pub struct Taa {
+    pub enabled: bool,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: Tracked<wgpu::RenderPipeline>,
+    uniform_buffer: Tracked<wgpu::Buffer>,
+    point_sampler: Tracked<wgpu::Sampler>,
+    linear_sampler: Tracked<wgpu::Sampler>,
+    history: Vec<Tracked<wgpu::Texture>>,
+    history_views: Vec<wgpu::TextureView>,
+    bind_groups: Vec<Tracked<wgpu::BindGroup>>,
+    current: usize,
+    history_valid: bool,
+    texel_size: [f32; 2],
+}
+
+impl Taa {
+    pub fn new(device: &wgpu::Device, targets: &RenderTargets) -> Taa {
+        let vs = include_bytes!("../../compiled_shaders/fullscreen.vert.spv");
+        let vs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+
+        let fs = include_bytes!("../../compiled_shaders/taa.frag.spv");
+        let fs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs[..])).unwrap());
+
+        let texture_binding = |binding| wgpu::BindGroupLayoutEntry {
+            binding: binding,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::SampledTexture {
+                multisampled: false,
+                component_type: wgpu::TextureComponentType::Float,
+                dimension: wgpu::TextureViewDimension::D2,
+            },
+        };
+        let sampler_binding = |binding| wgpu::BindGroupLayoutEntry {
+            binding: binding,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::Sampler { comparison: false },
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                texture_binding(0),
+                texture_binding(1),
+                texture_binding(2),
+                sampler_binding(3),
+                sampler_binding(4),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                },
+            ],
+            label: None,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_group_layout],
+        });
+
+        let color_state = wgpu::ColorStateDescriptor {
+            format: targets.format,
+            color_blend: wgpu::BlendDescriptor::REPLACE,
+            alpha_blend: wgpu::BlendDescriptor::REPLACE,
+            write_mask: wgpu::ColorWrite::ALL,
+        };
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            layout: &pipeline_layout,
+            vertex_stage: wgpu::ProgrammableStageDescriptor {
+                module: &vs_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                module: &fs_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: wgpu::CullMode::None,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
+                depth_bias_clamp: 0.0,
+            }),
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            // output to the swapchain and the next history texture
+            color_states: &[color_state.clone(), color_state],
+            depth_stencil_state: None,
+            vertex_state: wgpu::VertexStateDescriptor {
+                index_format: wgpu::IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<TaaUniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let mut taa = Taa {
+            enabled: true,
+            bind_group_layout: bind_group_layout,
+            pipeline: Tracked::new(pipeline),
+            uniform_buffer: Tracked::new(uniform_buffer),
+            point_sampler: Tracked::new(create_sampler(device, wgpu::FilterMode::Nearest)),
+            linear_sampler: Tracked::new(create_sampler(device, wgpu::FilterMode::Linear)),
+            history: Vec::new(),
+            history_views: Vec::new(),
+            bind_groups: Vec::new(),
+            current: 0,
+            history_valid: false,
+            texel_size: [0.0, 0.0],
+        };
+        taa.resize(device, targets);
+        taa
+    }
+
+    // Rebuilds the history textures, must be called whenever the render targets are recreated
+    pub fn resize(&mut self, device: &wgpu::Device, targets: &RenderTargets) {
+        let usage = wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_SRC;
+        self.history = (0..2).map(|_| Tracked::new(create_texture(device, targets.width, targets.height, targets.format, usage))).collect();
+        self.history_views = self.history.iter().map(|texture| texture.create_default_view()).collect();
+
+        // bind group `i` reads history `i` and the pass writes the other one
+        self.bind_groups = self.history_views.iter().map(|history_view| {
+            Tracked::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.bind_group_layout,
+                bindings: &[
+                    wgpu::Binding { binding: 0, resource: wgpu::BindingResource::TextureView(&targets.color_view) },
+                    wgpu::Binding { binding: 1, resource: wgpu::BindingResource::TextureView(&targets.velocity_view) },
+                    wgpu::Binding { binding: 2, resource: wgpu::BindingResource::TextureView(history_view) },
+                    wgpu::Binding { binding: 3, resource: wgpu::BindingResource::Sampler(&self.point_sampler) },
+                    wgpu::Binding { binding: 4, resource: wgpu::BindingResource::Sampler(&self.linear_sampler) },
+                    wgpu::Binding {
+                        binding: 5,
+                        resource: wgpu::BindingResource::Buffer {
+                            buffer: &self.uniform_buffer,
+                            range: 0..std::mem::size_of::<TaaUniforms>() as wgpu::BufferAddress,
+                        }
+                    },
+                ],
+                label: None,
+            }))
+        }).collect();
+
+        self.texel_size = [1.0 / targets.width.max(1) as f32, 1.0 / targets.height.max(1) as f32];
+        self.current = 0;
+        self.invalidate();
+    }
+
+    pub fn invalidate(&mut self) {
+        self.history_valid = false;
+    }
+
+    // The most recently resolved frame
+    pub fn history_texture(&self) -> &wgpu::Texture {
+        &self.history[self.current]
+    }
+
+    pub fn resolve(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView, stats: &mut FrameStats) {
+        let uniforms = TaaUniforms {
+            texel_size: self.texel_size,
+            blend: if self.enabled { TAA_BLEND } else { 1.0 },
+            history_valid: if self.history_valid { 1.0 } else { 0.0 },
+        };
+        let temp_buffer = device.create_buffer_with_data(uniforms.as_bytes(), wgpu::BufferUsage::COPY_SRC);
+        encoder.copy_buffer_to_buffer(&temp_buffer, 0, &self.uniform_buffer, 0, std::mem::size_of::<TaaUniforms>() as wgpu::BufferAddress);
+        stats.upload(std::mem::size_of::<TaaUniforms>());
+
+        let next = 1 - self.current;
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[
+                    wgpu::RenderPassColorAttachmentDescriptor {
+                        attachment: output,
+                        resolve_target: None,
+                        load_op: wgpu::LoadOp::Clear,
+                        store_op: wgpu::StoreOp::Store,
+                        clear_color: wgpu::Color::BLACK,
+                    },
+                    wgpu::RenderPassColorAttachmentDescriptor {
+                        attachment: &self.history_views[next],
+                        resolve_target: None,
+                        load_op: wgpu::LoadOp::Clear,
+                        store_op: wgpu::StoreOp::Store,
+                        clear_color: wgpu::Color::BLACK,
+                    },
+                ],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.bind_groups[self.current], &[]);
+            render_pass.draw(0..3, 0..1);
+            stats.draw(1);
+        }
+
+        self.current = next;
+        self.history_valid = true;
+    }
+}
+
+fn create_sampler(device: &wgpu::Device, filter: wgpu::FilterMode) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        lod_min_clamp: -100.0,
+        lod_max_clamp: 100.0,
+        compare: wgpu::CompareFunction::Undefined,
+    })
+}
@@ -0,0 +1,268 @@
+use super::stats::Tracked;
+
+pub const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+// float depth keeps its precision far away when used with reverse-Z
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+// Which end of the depth range is near
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DepthMode {
+    // 0 near, 1 far
+    Standard,
+    // 1 near, 0 far or infinitely far
+    Reversed,
+}
+
+impl DepthMode {
+    // Depth of an empty pixel
+    pub fn clear_depth(&self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::Reversed => 0.0,
+        }
+    }
+
+    // The comparison that does the same as `compare` does with standard depth
+    pub fn compare(&self, compare: wgpu::CompareFunction) -> wgpu::CompareFunction {
+        match (self, compare) {
+            (DepthMode::Reversed, wgpu::CompareFunction::Less) => wgpu::CompareFunction::Greater,
+            (DepthMode::Reversed, wgpu::CompareFunction::LessEqual) => wgpu::CompareFunction::GreaterEqual,
+            (DepthMode::Reversed, wgpu::CompareFunction::Greater) => wgpu::CompareFunction::Less,
+            (DepthMode::Reversed, wgpu::CompareFunction::GreaterEqual) => wgpu::CompareFunction::LessEqual,
+            (_, compare) => compare,
+        }
+    }
+}
+
+// Offscreen attachments the scene pass renders into before being resolved to the swapchain.
+// With MSAA the scene is drawn into `multisampled` and resolved into these.
+pub struct RenderTargets {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub color: Tracked<wgpu::Texture>,
+    pub color_view: wgpu::TextureView,
+    pub velocity: Tracked<wgpu::Texture>,
+    pub velocity_view: wgpu::TextureView,
+    pub depth: Tracked<wgpu::Texture>,
+    pub depth_view: wgpu::TextureView,
+    pub multisampled: Option<MultisampledTargets>,
+}
+
+pub struct MultisampledTargets {
+    pub samples: u32,
+    pub color: Tracked<wgpu::Texture>,
+    pub color_view: wgpu::TextureView,
+    pub velocity: Tracked<wgpu::Texture>,
+    pub velocity_view: wgpu::TextureView,
+    pub depth: Tracked<wgpu::Texture>,
+    pub depth_view: wgpu::TextureView,
+}
+
+impl RenderTargets {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, samples: u32) -> RenderTargets {
+        let usage = wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED;
+        let color = create_texture(device, width, height, format, usage | wgpu::TextureUsage::COPY_SRC);
+        let velocity = create_texture(device, width, height, VELOCITY_FORMAT, usage);
+        let depth = create_texture(device, width, height, DEPTH_FORMAT, wgpu::TextureUsage::OUTPUT_ATTACHMENT);
+
+        let multisampled = if samples > 1 {
+            let color = create_multisampled_texture(device, width, height, format, samples, wgpu::TextureUsage::OUTPUT_ATTACHMENT);
+            let velocity = create_multisampled_texture(device, width, height, VELOCITY_FORMAT, samples, wgpu::TextureUsage::OUTPUT_ATTACHMENT);
+            // sampled by the depth resolve
+            let depth = create_multisampled_texture(device, width, height, DEPTH_FORMAT, samples, usage);
+            Some(MultisampledTargets {
+                samples: samples,
+                color_view: color.create_default_view(),
+                color: Tracked::new(color),
+                velocity_view: velocity.create_default_view(),
+                velocity: Tracked::new(velocity),
+                depth_view: depth.create_default_view(),
+                depth: Tracked::new(depth),
+            })
+        } else {
+            None
+        };
+
+        RenderTargets {
+            width: width,
+            height: height,
+            format: format,
+            color_view: color.create_default_view(),
+            color: Tracked::new(color),
+            velocity_view: velocity.create_default_view(),
+            velocity: Tracked::new(velocity),
+            depth_view: depth.create_default_view(),
+            depth: Tracked::new(depth),
+            multisampled: multisampled,
+        }
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.multisampled.as_ref().map_or(1, |multisampled| multisampled.samples)
+    }
+}
+
+// Copies one sample of the multisampled depth into the single sample depth buffer. wgpu can't
+// resolve depth, and the grid and debug lines depth test against it after the TAA resolve.
+pub struct DepthResolve {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: Tracked<wgpu::RenderPipeline>,
+    sampler: Tracked<wgpu::Sampler>,
+}
+
+impl DepthResolve {
+    pub fn new(device: &wgpu::Device) -> DepthResolve {
+        let vs = include_bytes!("../../compiled_shaders/fullscreen.vert.spv");
+        let vs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+
+        let fs = include_bytes!("../../compiled_shaders/depth_resolve.frag.spv");
+        let fs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs[..])).unwrap());
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        multisampled: true,
+                        component_type: wgpu::TextureComponentType::Float,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+            ],
+            label: None,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_group_layout],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            layout: &pipeline_layout,
+            vertex_stage: wgpu::ProgrammableStageDescriptor {
+                module: &vs_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                module: &fs_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: wgpu::CullMode::None,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
+                depth_bias_clamp: 0.0,
+            }),
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            color_states: &[],
+            depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+                stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+                stencil_read_mask: 0,
+                stencil_write_mask: 0,
+            }),
+            vertex_state: wgpu::VertexStateDescriptor {
+                index_format: wgpu::IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Undefined,
+        });
+
+        DepthResolve {
+            bind_group_layout: bind_group_layout,
+            pipeline: Tracked::new(pipeline),
+            sampler: Tracked::new(sampler),
+        }
+    }
+
+    // Does nothing for single sampled targets, whose depth is already in place
+    pub fn encode(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, targets: &RenderTargets) {
+        let multisampled = match &targets.multisampled {
+            Some(multisampled) => multisampled,
+            None => return,
+        };
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            bindings: &[
+                wgpu::Binding { binding: 0, resource: wgpu::BindingResource::TextureView(&multisampled.depth_view) },
+                wgpu::Binding { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+            ],
+            label: None,
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                attachment: &targets.depth_view,
+                depth_load_op: wgpu::LoadOp::Clear,
+                depth_store_op: wgpu::StoreOp::Store,
+                clear_depth: 0.0,
+                stencil_load_op: wgpu::LoadOp::Clear,
+                stencil_store_op: wgpu::StoreOp::Store,
+                clear_stencil: 0,
+            }),
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+pub fn create_texture(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, usage: wgpu::TextureUsage) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth: 1,
+        },
+        array_layer_count: 1,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: format,
+        usage: usage,
+    })
+}
+
+fn create_multisampled_texture(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, samples: u32, usage: wgpu::TextureUsage) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth: 1,
+        },
+        array_layer_count: 1,
+        mip_level_count: 1,
+        sample_count: samples,
+        dimension: wgpu::TextureDimension::D2,
+        format: format,
+        usage: usage,
+    })
+}
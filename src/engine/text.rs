@@ -0,0 +1,674 @@
+use std::collections::HashMap;
+use glm::vec4;
+use rusttype::{Font, GlyphId, Scale, point};
+use zerocopy::{AsBytes, FromBytes};
+
+use super::stats::{FrameStats, Tracked};
+use super::types::Vector;
+use super::utils::array_to_matrix4;
+
+// proportional, with a kerning table
+const FONT_DATA: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+const ATLAS_SIZE: u32 = 1024;
+// Empty pixels between glyphs so linear filtering doesn't bleed neighbours in
+const ATLAS_PADDING: u32 = 1;
+// Solid block in the atlas corner, sampled by untextured rectangles
+const WHITE_SIZE: u32 = 4;
+
+pub type TextColor = [f32; 4];
+
+#[repr(C)]
+#[derive(Clone, Copy, AsBytes, FromBytes)]
+struct TextVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+const TEXT_VERTEX_SIZE: usize = std::mem::size_of::<TextVertex>();
+
+#[derive(Clone, Copy)]
+struct AtlasGlyph {
+    // uv rectangle in the atlas
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    // pixel offset of the bitmap from the pen position on the baseline
+    offset: [f32; 2],
+    size: [f32; 2],
+}
+
+enum Anchor {
+    Screen(f32, f32),
+    World(Vector),
+}
+
+struct RectItem {
+    min: [f32; 2],
+    max: [f32; 2],
+    color: TextColor,
+}
+
+struct TextItem {
+    text: String,
+    anchor: Anchor,
+    size: f32,
+    color: TextColor,
+    max_width: Option<f32>,
+}
+
+// Shelf packed glyph cache, rasterised on the CPU and re-uploaded whenever it changes
+struct GlyphAtlas {
+    pixels: Vec<u8>,
+    glyphs: HashMap<(GlyphId, u32), Option<AtlasGlyph>>,
+    cursor_x: u32,
+    cursor_y: u32,
+    row_height: u32,
+    dirty: bool,
+    // a glyph didn't fit and was left out
+    full: bool,
+}
+
+impl GlyphAtlas {
+    fn new() -> GlyphAtlas {
+        let mut atlas = GlyphAtlas {
+            pixels: vec![0; (ATLAS_SIZE * ATLAS_SIZE) as usize],
+            glyphs: HashMap::new(),
+            cursor_x: 0,
+            cursor_y: 0,
+            row_height: 0,
+            dirty: true,
+            full: false,
+        };
+        atlas.clear();
+        atlas
+    }
+
+    fn clear(&mut self) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = 0;
+        }
+        for y in 0..WHITE_SIZE {
+            for x in 0..WHITE_SIZE {
+                self.pixels[(y * ATLAS_SIZE + x) as usize] = 255;
+            }
+        }
+        self.glyphs.clear();
+        self.cursor_x = WHITE_SIZE + ATLAS_PADDING;
+        self.cursor_y = 0;
+        self.row_height = WHITE_SIZE;
+        self.dirty = true;
+        self.full = false;
+    }
+
+    fn white_uv() -> [f32; 2] {
+        let center = WHITE_SIZE as f32 * 0.5 / ATLAS_SIZE as f32;
+        [center, center]
+    }
+
+    // None for glyphs without any coverage, like spaces, glyphs larger than the atlas, and
+    // glyphs that no longer fit, which set `full`. `size` is in whole pixels.
+    fn get(&mut self, font: &Font<'static>, id: GlyphId, size: f32) -> Option<AtlasGlyph> {
+        let key = (id, size as u32);
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+
+        let glyph = font.glyph(id).scaled(Scale::uniform(size)).positioned(point(0.0, 0.0));
+        let bounds = match glyph.pixel_bounding_box() {
+            Some(bounds) => bounds,
+            None => {
+                self.glyphs.insert(key, None);
+                return None;
+            }
+        };
+        let width = bounds.width() as u32;
+        let height = bounds.height() as u32;
+        if width + ATLAS_PADDING > ATLAS_SIZE || height + ATLAS_PADDING > ATLAS_SIZE {
+            self.glyphs.insert(key, None);
+            return None;
+        }
+
+        if self.cursor_x + width + ATLAS_PADDING > ATLAS_SIZE {
+            self.cursor_x = 0;
+            self.cursor_y += self.row_height + ATLAS_PADDING;
+            self.row_height = 0;
+        }
+        if self.cursor_y + height + ATLAS_PADDING > ATLAS_SIZE {
+            // glyphs already laid out this frame point into the atlas, so it's only cleared
+            // between layouts
+            self.full = true;
+            return None;
+        }
+
+        let (x0, y0) = (self.cursor_x, self.cursor_y);
+        let pixels = &mut self.pixels;
+        glyph.draw(|x, y, coverage| {
+            let index = ((y0 + y) * ATLAS_SIZE + x0 + x) as usize;
+            pixels[index] = (coverage * 255.0) as u8;
+        });
+
+        self.cursor_x += width + ATLAS_PADDING;
+        self.row_height = self.row_height.max(height);
+        self.dirty = true;
+
+        let atlas_glyph = AtlasGlyph {
+            uv_min: [x0 as f32 / ATLAS_SIZE as f32, y0 as f32 / ATLAS_SIZE as f32],
+            uv_max: [(x0 + width) as f32 / ATLAS_SIZE as f32, (y0 + height) as f32 / ATLAS_SIZE as f32],
+            offset: [bounds.min.x as f32, bounds.min.y as f32],
+            size: [width as f32, height as f32],
+        };
+        self.glyphs.insert(key, Some(atlas_glyph));
+        Some(atlas_glyph)
+    }
+}
+
+// The font and how strings are placed with it
+struct TextLayout {
+    font: Font<'static>,
+}
+
+impl TextLayout {
+    fn new() -> TextLayout {
+        TextLayout {
+            font: Font::from_bytes(FONT_DATA).expect("Failed to load the built-in font"),
+        }
+    }
+
+    // Width and height in pixels of a laid out string, at a size in whole pixels
+    fn measure(&self, text: &str, size: f32, max_width: Option<f32>) -> (f32, f32) {
+        let mut width: f32 = 0.0;
+        let mut height: f32 = 0.0;
+        for (glyph_id, x, y) in self.layout(text, size, max_width) {
+            let advance = self.font.glyph(glyph_id).scaled(Scale::uniform(size)).h_metrics().advance_width;
+            width = width.max(x + advance);
+            height = height.max(y);
+        }
+        (width, height + self.line_height(size))
+    }
+
+    fn line_height(&self, size: f32) -> f32 {
+        let v_metrics = self.font.v_metrics(Scale::uniform(size));
+        v_metrics.ascent - v_metrics.descent + v_metrics.line_gap
+    }
+
+    // Pen positions relative to the top left of the text, applying kerning and word wrapping
+    fn layout(&self, text: &str, size: f32, max_width: Option<f32>) -> Vec<(GlyphId, f32, f32)> {
+        let scale = Scale::uniform(size);
+        let line_height = self.line_height(size);
+        let mut glyphs = Vec::new();
+        let mut y = 0.0;
+
+        for line in text.lines() {
+            let mut x = 0.0;
+            let mut previous: Option<GlyphId> = None;
+
+            for (i, word) in line.split(' ').enumerate() {
+                let word = if i == 0 { word.to_string() } else { format!(" {}", word) };
+                let word_width = self.advance(&word, scale, previous);
+
+                let mut wrapped = false;
+                if let Some(max_width) = max_width {
+                    if x > 0.0 && x + word_width > max_width {
+                        x = 0.0;
+                        y += line_height;
+                        previous = None;
+                        wrapped = true;
+                    }
+                }
+
+                // a wrapped word doesn't keep its leading space
+                let chars = if wrapped { word.trim_start().to_string() } else { word };
+                for ch in chars.chars() {
+                    let glyph = self.font.glyph(ch);
+                    let id = glyph.id();
+                    if let Some(previous) = previous {
+                        x += self.font.pair_kerning(scale, previous, id);
+                    }
+                    glyphs.push((id, x, y));
+                    x += glyph.scaled(scale).h_metrics().advance_width;
+                    previous = Some(id);
+                }
+            }
+
+            y += line_height;
+        }
+
+        glyphs
+    }
+
+    fn advance(&self, text: &str, scale: Scale, mut previous: Option<GlyphId>) -> f32 {
+        let mut width = 0.0;
+        for ch in text.chars() {
+            let glyph = self.font.glyph(ch);
+            let id = glyph.id();
+            if let Some(previous) = previous {
+                width += self.font.pair_kerning(scale, previous, id);
+            }
+            width += glyph.scaled(scale).h_metrics().advance_width;
+            previous = Some(id);
+        }
+        width
+    }
+}
+
+// Screen and world space text overlay, queued per frame like the debug lines
+pub struct TextRenderer {
+    pub enabled: bool,
+    layout: TextLayout,
+    atlas: GlyphAtlas,
+    items: Vec<TextItem>,
+    rects: Vec<RectItem>,
+    atlas_texture: Tracked<wgpu::Texture>,
+    // only read through the bind group, kept so it's counted for as long as it's bound
+    _sampler: Tracked<wgpu::Sampler>,
+    uniform_buffer: Tracked<wgpu::Buffer>,
+    bind_group: Tracked<wgpu::BindGroup>,
+    pipeline: Tracked<wgpu::RenderPipeline>,
+}
+
+impl TextRenderer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> TextRenderer {
+        let vs = include_bytes!("../../compiled_shaders/text.vert.spv");
+        let vs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+
+        let fs = include_bytes!("../../compiled_shaders/text.frag.spv");
+        let fs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs[..])).unwrap());
+
+        let atlas_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth: 1,
+            },
+            array_layer_count: 1,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Undefined,
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 16,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        multisampled: false,
+                        component_type: wgpu::TextureComponentType::Float,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::VERTEX,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                },
+            ],
+            label: None,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&atlas_texture.create_default_view()),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::Binding {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: &uniform_buffer,
+                        range: 0..16,
+                    }
+                },
+            ],
+            label: None,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_group_layout],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            layout: &pipeline_layout,
+            vertex_stage: wgpu::ProgrammableStageDescriptor {
+                module: &vs_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                module: &fs_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: wgpu::CullMode::None,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
+                depth_bias_clamp: 0.0,
+            }),
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            color_states: &[wgpu::ColorStateDescriptor {
+                format: format,
+                color_blend: wgpu::BlendDescriptor {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            depth_stencil_state: None,
+            vertex_state: wgpu::VertexStateDescriptor {
+                index_format: wgpu::IndexFormat::Uint16,
+                vertex_buffers: &[wgpu::VertexBufferDescriptor {
+                    stride: TEXT_VERTEX_SIZE as wgpu::BufferAddress,
+                    step_mode: wgpu::InputStepMode::Vertex,
+                    attributes: &[
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float2,
+                            offset: 0,
+                            shader_location: 0,
+                        },
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float2,
+                            offset: 4 * 2,
+                            shader_location: 1,
+                        },
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float4,
+                            offset: 4 * 4,
+                            shader_location: 2,
+                        },
+                    ],
+                }],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        TextRenderer {
+            enabled: true,
+            layout: TextLayout::new(),
+            atlas: GlyphAtlas::new(),
+            items: Vec::new(),
+            rects: Vec::new(),
+            atlas_texture: Tracked::new(atlas_texture),
+            _sampler: Tracked::new(sampler),
+            uniform_buffer: Tracked::new(uniform_buffer),
+            bind_group: Tracked::new(bind_group),
+            pipeline: Tracked::new(pipeline),
+        }
+    }
+
+    // Text with its top left corner at `x`, `y` pixels from the top left of the window
+    pub fn draw(&mut self, text: &str, x: f32, y: f32, size: f32, color: TextColor) {
+        self.queue(text, Anchor::Screen(x, y), size, color, None);
+    }
+
+    // Like `draw`, wrapping words onto new lines past `max_width` pixels
+    pub fn draw_wrapped(&mut self, text: &str, x: f32, y: f32, size: f32, color: TextColor, max_width: f32) {
+        self.queue(text, Anchor::Screen(x, y), size, color, Some(max_width));
+    }
+
+    // Text centered on the screen position of a point in the world
+    pub fn draw_world(&mut self, text: &str, position: &Vector, size: f32, color: TextColor) {
+        self.queue(text, Anchor::World(*position), size, color, None);
+    }
+
+    // Solid screen space rectangle drawn underneath the text, for panels and graphs
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: TextColor) {
+        self.rects.push(RectItem {
+            min: [x, y],
+            max: [x + width, y + height],
+            color: color,
+        });
+    }
+
+    // Width and height in pixels of a laid out string
+    pub fn measure(&self, text: &str, size: f32, max_width: Option<f32>) -> (f32, f32) {
+        self.layout.measure(text, pixel_size(size), max_width)
+    }
+
+    pub fn render(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView, width: u32, height: u32, view_projection: &[f32; 16], stats: &mut FrameStats) {
+        let items = std::mem::replace(&mut self.items, Vec::new());
+        let rects = std::mem::replace(&mut self.rects, Vec::new());
+        if !self.enabled || (items.is_empty() && rects.is_empty()) {
+            return;
+        }
+
+        let view_projection = array_to_matrix4(view_projection);
+        let mut vertices = self.build_vertices(&items, &rects, width, height, &view_projection);
+        if self.atlas.full {
+            // start over with only the glyphs this frame uses, whatever still doesn't fit is
+            // left out
+            self.atlas.clear();
+            vertices = self.build_vertices(&items, &rects, width, height, &view_projection);
+        }
+
+        if vertices.is_empty() {
+            return;
+        }
+
+        if self.atlas.dirty {
+            let atlas_buffer = device.create_buffer_with_data(&self.atlas.pixels, wgpu::BufferUsage::COPY_SRC);
+            encoder.copy_buffer_to_texture(
+                wgpu::BufferCopyView {
+                    buffer: &atlas_buffer,
+                    offset: 0,
+                    bytes_per_row: ATLAS_SIZE,
+                    rows_per_image: ATLAS_SIZE,
+                },
+                wgpu::TextureCopyView {
+                    texture: &self.atlas_texture,
+                    mip_level: 0,
+                    array_layer: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                wgpu::Extent3d {
+                    width: ATLAS_SIZE,
+                    height: ATLAS_SIZE,
+                    depth: 1,
+                },
+            );
+            self.atlas.dirty = false;
+            stats.upload(self.atlas.pixels.len());
+        }
+
+        let screen_size = [width as f32, height as f32, 0.0, 0.0];
+        let temp_buffer = device.create_buffer_with_data(screen_size.as_bytes(), wgpu::BufferUsage::COPY_SRC);
+        encoder.copy_buffer_to_buffer(&temp_buffer, 0, &self.uniform_buffer, 0, 16);
+        stats.upload(16);
+
+        let vertex_buffer = device.create_buffer_with_data(vertices.as_bytes(), wgpu::BufferUsage::VERTEX);
+        stats.upload(vertices.as_bytes().len());
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: output,
+                resolve_target: None,
+                load_op: wgpu::LoadOp::Load,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::BLACK,
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, &vertex_buffer, 0, 0);
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+        stats.draw(vertices.len() as u32 / 3);
+    }
+
+    fn build_vertices(&mut self, items: &[TextItem], rects: &[RectItem], width: u32, height: u32, view_projection: &glm::Mat4) -> Vec<TextVertex> {
+        let mut vertices: Vec<TextVertex> = Vec::new();
+
+        let white = GlyphAtlas::white_uv();
+        for rect in rects.iter() {
+            let vertex = |px, py| TextVertex { position: [px, py], uv: white, color: rect.color };
+            let ([x0, y0], [x1, y1]) = (rect.min, rect.max);
+            vertices.extend_from_slice(&[
+                vertex(x0, y0), vertex(x1, y0), vertex(x1, y1),
+                vertex(x1, y1), vertex(x0, y1), vertex(x0, y0),
+            ]);
+        }
+
+        for item in items.iter() {
+            let origin = match &item.anchor {
+                Anchor::Screen(x, y) => (*x, *y),
+                Anchor::World(position) => {
+                    let clip = *view_projection * vec4(position.x, position.y, position.z, 1.0);
+                    if clip.w <= 0.0 {
+                        // behind the camera
+                        continue;
+                    }
+                    let (text_width, text_height) = self.measure(&item.text, item.size, item.max_width);
+                    let screen_x = (clip.x / clip.w * 0.5 + 0.5) * width as f32;
+                    let screen_y = (0.5 - clip.y / clip.w * 0.5) * height as f32;
+                    (screen_x - text_width * 0.5, screen_y - text_height * 0.5)
+                }
+            };
+
+            let ascent = self.layout.font.v_metrics(Scale::uniform(item.size)).ascent;
+            for (glyph_id, x, y) in self.layout.layout(&item.text, item.size, item.max_width) {
+                let glyph = match self.atlas.get(&self.layout.font, glyph_id, item.size) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                let x0 = (origin.0 + x).round() + glyph.offset[0];
+                let y0 = (origin.1 + y + ascent).round() + glyph.offset[1];
+                let x1 = x0 + glyph.size[0];
+                let y1 = y0 + glyph.size[1];
+                let (u0, v0, u1, v1) = (glyph.uv_min[0], glyph.uv_min[1], glyph.uv_max[0], glyph.uv_max[1]);
+
+                let vertex = |px, py, u, v| TextVertex { position: [px, py], uv: [u, v], color: item.color };
+                vertices.extend_from_slice(&[
+                    vertex(x0, y0, u0, v0), vertex(x1, y0, u1, v0), vertex(x1, y1, u1, v1),
+                    vertex(x1, y1, u1, v1), vertex(x0, y1, u0, v1), vertex(x0, y0, u0, v0),
+                ]);
+            }
+        }
+
+        vertices
+    }
+
+    fn queue(&mut self, text: &str, anchor: Anchor, size: f32, color: TextColor, max_width: Option<f32>) {
+        self.items.push(TextItem {
+            text: text.to_string(),
+            anchor: anchor,
+            size: pixel_size(size),
+            color: color,
+            max_width: max_width,
+        });
+    }
+}
+
+// Glyphs are rasterised at whole pixel sizes, layout uses the same size so they line up
+fn pixel_size(size: f32) -> f32 {
+    size.round().max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: f32 = 20.0;
+
+    fn width(layout: &TextLayout, text: &str) -> f32 {
+        layout.measure(text, SIZE, None).0
+    }
+
+    #[test]
+    fn kerning_pulls_pairs_together() {
+        let layout = TextLayout::new();
+        let kerned = width(&layout, "AV");
+        let apart = width(&layout, "A") + width(&layout, "V");
+        assert!(kerned < apart - 0.5, "{} is not kerned from {}", kerned, apart);
+
+        let glyphs = layout.layout("AV", SIZE, None);
+        assert!(glyphs[1].1 < width(&layout, "A"));
+    }
+
+    #[test]
+    fn glyphs_advance_along_a_line() {
+        let layout = TextLayout::new();
+        let glyphs = layout.layout("il WM", SIZE, None);
+        assert_eq!(glyphs.len(), 5);
+        assert!(glyphs.windows(2).all(|pair| pair[1].1 > pair[0].1));
+        assert!(glyphs.iter().all(|glyph| glyph.2 == 0.0));
+        // proportional, a narrow letter takes less room than a wide one
+        assert!(width(&layout, "i") < width(&layout, "W"));
+    }
+
+    #[test]
+    fn line_breaks_start_a_new_line() {
+        let layout = TextLayout::new();
+        let glyphs = layout.layout("ab\ncd", SIZE, None);
+        let line_height = layout.line_height(SIZE);
+        assert_eq!(glyphs.len(), 4);
+        assert_eq!((glyphs[2].1, glyphs[2].2), (0.0, line_height));
+        assert_eq!(layout.measure("ab\ncd", SIZE, None).1, line_height * 2.0);
+    }
+
+    #[test]
+    fn words_wrap_past_the_width() {
+        let layout = TextLayout::new();
+        let max_width = width(&layout, "wall door") - 1.0;
+        let glyphs = layout.layout("wall door", SIZE, Some(max_width));
+
+        // the space before the wrapped word is dropped
+        assert_eq!(glyphs.len(), 8);
+        assert!(glyphs[..4].iter().all(|glyph| glyph.2 == 0.0));
+        assert_eq!((glyphs[4].1, glyphs[4].2), (0.0, layout.line_height(SIZE)));
+
+        let (wrapped_width, wrapped_height) = layout.measure("wall door", SIZE, Some(max_width));
+        assert!(wrapped_width <= max_width);
+        assert_eq!(wrapped_height, layout.line_height(SIZE) * 2.0);
+    }
+
+    #[test]
+    fn text_that_fits_stays_on_one_line() {
+        let layout = TextLayout::new();
+        let max_width = width(&layout, "wall door") + 1.0;
+        let glyphs = layout.layout("wall door", SIZE, Some(max_width));
+        assert_eq!(glyphs.len(), 9);
+        assert!(glyphs.iter().all(|glyph| glyph.2 == 0.0));
+    }
+
+    #[test]
+    fn a_word_wider_than_the_line_is_not_split() {
+        let layout = TextLayout::new();
+        let glyphs = layout.layout("staircase", SIZE, Some(10.0));
+        assert!(glyphs.iter().all(|glyph| glyph.2 == 0.0));
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::Instant;
+
+// Simulation rate, independent of how fast frames are rendered
+pub const FIXED_DT: f32 = 1.0 / 120.0;
+// Longest real frame time fed into the accumulator, a slow frame won't queue up
+// more ticks than this covers (the spiral of death)
+const MAX_FRAME_TIME: f32 = 0.25;
+
+// Accumulates real time and hands it out in fixed size simulation ticks
+pub struct FixedTimestep {
+    pub dt: f32,
+    accumulator: f32,
+    last: Instant,
+}
+
+impl FixedTimestep {
+    pub fn new(dt: f32) -> FixedTimestep {
+        FixedTimestep {
+            dt: dt,
+            accumulator: 0.0,
+            last: Instant::now(),
+        }
+    }
+
+    // Number of ticks to simulate for the time elapsed since the last call
+    pub fn advance(&mut self) -> u32 {
+        let frame_time = self.last.elapsed().as_secs_f32().min(MAX_FRAME_TIME);
+        self.advance_by(frame_time)
+    }
+
+    // Like `advance`, but for a simulated frame time instead of the real one
+    pub fn advance_by(&mut self, frame_time: f32) -> u32 {
+        self.last = Instant::now();
+
+        self.accumulator += frame_time;
+        let ticks = (self.accumulator / self.dt).floor();
+        self.accumulator -= ticks * self.dt;
+        ticks as u32
+    }
+
+    // Forgets the time spent sleeping, so an idle loop doesn't catch up on ticks when woken
+    pub fn resume(&mut self) {
+        self.last = Instant::now();
+    }
+
+    // How far between the last two ticks the rendered frame lies, in [0, 1)
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.dt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn advance_clamps_long_frames() {
+        let mut timestep = FixedTimestep::new(FIXED_DT);
+        // a second-long stall only catches up on a quarter of a second
+        timestep.last = Instant::now() - Duration::from_secs(1);
+        assert_eq!(timestep.advance(), (MAX_FRAME_TIME / FIXED_DT).floor() as u32);
+        assert!(timestep.alpha() < 1.0);
+    }
+
+    #[test]
+    fn advance_by_carries_the_remainder() {
+        let mut timestep = FixedTimestep::new(0.25);
+        assert_eq!(timestep.advance_by(0.6), 2);
+        assert!((timestep.alpha() - 0.4).abs() < 1e-5);
+        assert_eq!(timestep.advance_by(0.15), 1);
+        assert!(timestep.alpha().abs() < 1e-5);
+    }
+
+    #[test]
+    fn resume_forgets_the_idle_time() {
+        let mut timestep = FixedTimestep::new(0.5);
+        timestep.last = Instant::now() - Duration::from_secs(1);
+        timestep.resume();
+        assert_eq!(timestep.advance(), 0);
+    }
+}
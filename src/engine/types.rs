@@ -1,11 +1,17 @@
 // use glm::{vec2, vec3, Vector2, Vector3};
+use std::ops::{Add, Sub, Mul, Neg};
 use zerocopy::{AsBytes, FromBytes};
 
+use super::quaternion::Quaternion;
+use super::utils::{transform, matrix4_to_array};
+
 #[repr(C)]
 #[derive(Clone, Copy, AsBytes, FromBytes)]
 pub struct Vertex {
     position: [f32; 3],
     color: [f32; 3],
+    normal: [f32; 3],
+    uv: [f32; 2],
 }
 
 pub const VERTEX_SIZE: usize = std::mem::size_of::<Vertex>();
@@ -14,7 +20,9 @@ impl Vertex {
     pub fn new(x: f32, y: f32, z: f32, r: f32, g: f32, b: f32) -> Vertex {
         Vertex {
             position: [x, y, z],
-            color: [r, g, b]
+            color: [r, g, b],
+            normal: [0.0, 0.0, 0.0],
+            uv: [0.0, 0.0],
         }
     }
 
@@ -22,11 +30,65 @@ impl Vertex {
         Vertex {
             position: position,
             color: color,
+            normal: [0.0, 0.0, 0.0],
+            uv: [0.0, 0.0],
+        }
+    }
+
+    pub fn position(&self) -> Vector {
+        Vector::new(self.position[0], self.position[1], self.position[2])
+    }
+
+    pub fn with_normal_uv(mut self, normal: [f32; 3], uv: [f32; 2]) -> Vertex {
+        self.normal = normal;
+        self.uv = uv;
+        self
+    }
+}
+
+// Layout must match the Locals block in shader.vert
+#[repr(C)]
+#[derive(Clone, Copy, AsBytes, FromBytes)]
+pub struct Uniforms {
+    pub transform: [f32; 16],
+    pub view_projection: [f32; 16],
+    pub prev_view_projection: [f32; 16],
+    pub jitter: [f32; 4],
+    // near, far, mesh id, light count
+    pub params: [f32; 4],
+    // 1 if depth is reversed with an infinite far plane, then unused
+    pub depth: [f32; 4],
+    // object to world, this frame and the last
+    pub model: [f32; 16],
+    pub prev_model: [f32; 16],
+}
+
+pub const UNIFORMS_SIZE: usize = std::mem::size_of::<Uniforms>();
+
+pub const IDENTITY: [f32; 16] = [
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 1.0, 0.0,
+    0.0, 0.0, 0.0, 1.0,
+];
+
+impl Uniforms {
+    // Unjittered and without motion, for single frames rendered outside the TAA loop
+    pub fn still(view_projection: [f32; 16], near: f32, far: f32) -> Uniforms {
+        Uniforms {
+            transform: view_projection,
+            view_projection: view_projection,
+            prev_view_projection: view_projection,
+            jitter: [0.0; 4],
+            params: [near, far, 0.0, 0.0],
+            depth: [0.0; 4],
+            model: IDENTITY,
+            prev_model: IDENTITY,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vector {
     pub x: f32,
     pub y: f32,
@@ -37,5 +99,107 @@ impl Vector {
     pub fn new(x: f32, y: f32, z: f32) -> Vector {
         Vector { x: x, y: y, z: z }
     }
+
+    pub fn distance(&self, other: &Vector) -> f32 {
+        let dx = self.x - other.x;
+        let dy = self.y - other.y;
+        let dz = self.z - other.z;
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
+
+    pub fn dot(&self, other: &Vector) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: &Vector) -> Vector {
+        Vector::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    // Unit vector in the same direction, the zero vector stays zero
+    pub fn normalize(&self) -> Vector {
+        let length = self.length();
+        if length > 0.0 {
+            *self * (1.0 / length)
+        } else {
+            *self
+        }
+    }
+}
+
+impl Add for Vector {
+    type Output = Vector;
+
+    fn add(self, other: Vector) -> Vector {
+        Vector::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Vector {
+    type Output = Vector;
+
+    fn sub(self, other: Vector) -> Vector {
+        Vector::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
 }
 
+impl Neg for Vector {
+    type Output = Vector;
+
+    fn neg(self) -> Vector {
+        Vector::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl Mul<f32> for Vector {
+    type Output = Vector;
+
+    fn mul(self, scale: f32) -> Vector {
+        Vector::new(self.x * scale, self.y * scale, self.z * scale)
+    }
+}
+
+
+// Where an object is and which way it faces
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub position: Vector,
+    pub orientation: Quaternion,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            position: Vector::new(0.0, 0.0, 0.0),
+            orientation: Quaternion::identity(),
+        }
+    }
+
+    // Radians around x, then y, then z, as in `Quaternion::from_euler`
+    pub fn from_euler(position: Vector, rotation: &Vector) -> Transform {
+        Transform {
+            position: position,
+            orientation: Quaternion::from_euler(rotation),
+        }
+    }
+
+    pub fn rotation(&self) -> Vector {
+        self.orientation.to_euler()
+    }
+
+    pub fn set_rotation(&mut self, rotation: &Vector) {
+        self.orientation = Quaternion::from_euler(rotation);
+    }
+
+    // Object to world
+    pub fn matrix(&self) -> [f32; 16] {
+        matrix4_to_array(transform(&self.position, &self.orientation))
+    }
+}
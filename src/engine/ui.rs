@@ -0,0 +1,201 @@
+use std::time::Instant;
+use imgui::{im_str, Condition, ImString, Slider};
+use imgui_winit_support::{HiDpiMode, WinitPlatform};
+use winit::{event::Event, window::Window};
+
+use super::actions::{Action, ActionMap, Input, ACTIONS};
+use super::animation::Pose;
+use super::camera::Camera;
+use super::pacing::RenderMode;
+use super::stats::Stats;
+use super::status::StatusLog;
+use super::types::Vector;
+use super::visualization::{VisualizationMode, MODES};
+
+// Renderer switches exposed in the UI, borrowed from the engine for one frame
+pub struct RendererToggles<'a> {
+    pub taa: &'a mut bool,
+    pub grid: &'a mut bool,
+    pub debug_lines: &'a mut bool,
+    pub text: &'a mut bool,
+    pub visualization_mode: &'a mut VisualizationMode,
+    // shown only, switched with their hotkeys
+    pub present_mode: wgpu::PresentMode,
+    pub render_mode: RenderMode,
+}
+
+// Dear ImGui overlay drawn after the scene
+pub struct DebugUi {
+    pub visible: bool,
+    context: imgui::Context,
+    platform: WinitPlatform,
+    // imgui-wgpu doesn't expose its GPU objects, so they're left out of the live resource counts
+    renderer: imgui_wgpu::Renderer,
+    last_frame: Instant,
+}
+
+impl DebugUi {
+    pub fn new(window: &Window, device: &wgpu::Device, queue: &mut wgpu::Queue, format: wgpu::TextureFormat) -> DebugUi {
+        let mut context = imgui::Context::create();
+        context.set_ini_filename(None);
+
+        let mut platform = WinitPlatform::init(&mut context);
+        platform.attach_window(context.io_mut(), window, HiDpiMode::Default);
+
+        let font_size = (13.0 * platform.hidpi_factor()) as f32;
+        context.io_mut().font_global_scale = (1.0 / platform.hidpi_factor()) as f32;
+        context.fonts().add_font(&[imgui::FontSource::DefaultFontData {
+            config: Some(imgui::FontConfig {
+                size_pixels: font_size,
+                ..imgui::FontConfig::default()
+            }),
+        }]);
+
+        let renderer = imgui_wgpu::Renderer::new(&mut context, device, queue, format, None);
+
+        DebugUi {
+            visible: true,
+            context: context,
+            platform: platform,
+            renderer: renderer,
+            last_frame: Instant::now(),
+        }
+    }
+
+    pub fn handle_event(&mut self, window: &Window, event: &Event<()>) {
+        self.platform.handle_event(self.context.io_mut(), window, event);
+    }
+
+    // True while a widget has keyboard focus, the camera shouldn't see those keys
+    pub fn wants_keyboard(&self) -> bool {
+        self.visible && self.context.io().want_capture_keyboard
+    }
+
+    pub fn wants_mouse(&self) -> bool {
+        self.visible && self.context.io().want_capture_mouse
+    }
+
+    pub fn render<F: FnOnce(&imgui::Ui)>(&mut self, window: &Window, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView, build: F) {
+        self.last_frame = self.context.io_mut().update_delta_time(self.last_frame);
+
+        if !self.visible {
+            return;
+        }
+
+        self.platform.prepare_frame(self.context.io_mut(), window).expect("Failed to prepare the UI frame");
+        let ui = self.context.frame();
+        build(&ui);
+        self.platform.prepare_render(&ui, window);
+
+        self.renderer.render(ui.render(), device, encoder, output).expect("Failed to render the UI");
+    }
+}
+
+// True if the camera was moved, so whatever controls it can take over from there
+pub fn camera_panel(ui: &imgui::Ui, camera: &mut Camera) -> bool {
+    let mut moved = false;
+    imgui::Window::new(im_str!("Camera"))
+        .position([10.0, 60.0], Condition::FirstUseEver)
+        .size([300.0, 200.0], Condition::FirstUseEver)
+        .build(ui, || {
+            let mut position = [camera.position.x, camera.position.y, camera.position.z];
+            let position_changed = ui.input_float3(im_str!("position"), &mut position).build();
+
+            let mut yaw = camera.yaw().to_degrees();
+            let mut pitch = camera.pitch().to_degrees();
+            let yaw_changed = ui.input_float(im_str!("yaw"), &mut yaw).build();
+            let pitch_changed = Slider::new(im_str!("pitch"), -89.0..=89.0).build(ui, &mut pitch);
+            if position_changed || yaw_changed || pitch_changed {
+                let position = Vector::new(position[0], position[1], position[2]);
+                camera.jump_to(&Pose::from_yaw_pitch(position, yaw.to_radians(), pitch.max(-89.0).min(89.0).to_radians()));
+                moved = true;
+            }
+
+            Slider::new(im_str!("fov"), 10.0..=170.0).build(ui, &mut camera.fov);
+            let (mut near, mut far) = (camera.near, camera.far);
+            let near_changed = ui.input_float(im_str!("near"), &mut near).build();
+            let far_changed = ui.input_float(im_str!("far"), &mut far).build();
+            if near_changed || far_changed {
+                camera.set_clip_planes(near, far);
+            }
+            ui.checkbox(im_str!("reverse-Z"), &mut camera.reverse_z);
+            Slider::new(im_str!("speed"), 0.1..=50.0).build(ui, &mut camera.speed);
+            Slider::new(im_str!("rotation speed"), 0.1..=10.0).build(ui, &mut camera.rotation_speed);
+
+            if ui.small_button(im_str!("reset")) {
+                camera.reset();
+                moved = true;
+            }
+        });
+    moved
+}
+
+// Every action with its inputs, `bind` adds whichever key or button is pressed next
+pub fn controls_panel(ui: &imgui::Ui, actions: &mut ActionMap, rebinding: &mut Option<Action>, status: &mut StatusLog) {
+    imgui::Window::new(im_str!("Controls"))
+        .position([320.0, 60.0], Condition::FirstUseEver)
+        .size([360.0, 400.0], Condition::FirstUseEver)
+        .build(ui, || {
+            if let Some(action) = rebinding {
+                ui.text(format!("press a key or button for {}, Escape cancels", action.name()));
+                ui.separator();
+            }
+
+            for (index, (action, name)) in ACTIONS.iter().enumerate() {
+                let id = ui.push_id(index as i32);
+                let inputs = actions.inputs(*action).iter().map(Input::to_string).collect::<Vec<_>>();
+                ui.text(format!("{}: {}", name, inputs.join(", ")));
+                ui.same_line(250.0);
+                if ui.small_button(im_str!("bind")) {
+                    *rebinding = Some(*action);
+                }
+                ui.same_line(0.0);
+                if ui.small_button(im_str!("clear")) {
+                    actions.clear(*action);
+                }
+                ui.same_line(0.0);
+                if ui.small_button(im_str!("reset")) {
+                    for conflict in actions.reset(*action) {
+                        status.push(format!("Not rebound, {}", conflict));
+                    }
+                }
+                id.pop(ui);
+            }
+        });
+}
+
+pub fn timings_panel(ui: &imgui::Ui, stats: &Stats) {
+    imgui::Window::new(im_str!("Frame timings"))
+        .position([10.0, 270.0], Condition::FirstUseEver)
+        .size([300.0, 130.0], Condition::FirstUseEver)
+        .build(ui, || {
+            let average = stats.average_frame_ms();
+            ui.text(format!("frame {:.2} ms, average {:.2} ms ({:.0} fps)", stats.last().frame_ms, average, 1000.0 / average.max(0.001)));
+            let frame_times: Vec<f32> = stats.history().map(|frame| frame.frame_ms).collect();
+            ui.plot_lines(im_str!("ms"), &frame_times)
+                .scale_min(0.0)
+                .graph_size([0.0, 60.0])
+                .build();
+        });
+}
+
+pub fn renderer_panel(ui: &imgui::Ui, toggles: RendererToggles) {
+    imgui::Window::new(im_str!("Renderer"))
+        .position([10.0, 410.0], Condition::FirstUseEver)
+        .size([300.0, 260.0], Condition::FirstUseEver)
+        .build(ui, || {
+            ui.checkbox(im_str!("temporal anti-aliasing"), toggles.taa);
+            ui.checkbox(im_str!("ground grid"), toggles.grid);
+            ui.checkbox(im_str!("debug lines"), toggles.debug_lines);
+            ui.checkbox(im_str!("text"), toggles.text);
+            ui.text(format!("present mode: {:?} (requested)", toggles.present_mode));
+            ui.text(format!("render mode: {:?}", toggles.render_mode));
+
+            ui.separator();
+            ui.text("visualization");
+            for mode in MODES.iter() {
+                let label = ImString::new(format!("{:?}", mode));
+                ui.radio_button(&label, toggles.visualization_mode, *mode);
+            }
+        });
+}
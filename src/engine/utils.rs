@@ -1,38 +1,41 @@
 use std::convert::TryInto;
 use std::f32::consts::PI;
 use glm::{mat4, Matrix4};
+use super::quaternion::Quaternion;
 use super::types::{Vector};
 
 pub fn deg_to_rad(deg: f32) -> f32 {
     (deg * PI) / 180.0
 }
 
+// Same rotation as `Quaternion::from_euler`
 pub fn rotation(v: &Vector) -> Matrix4<f32> {
     xrotation(v.x) * yrotation(v.y) * zrotation(v.z)
 }
 
-pub fn trasform(pos: &Vector, rot: &Vector) -> Matrix4<f32> {
-    let x_sin = rot.x.sin();
-    let x_cos = rot.x.cos();
-    let y_sin = rot.y.sin();
-    let y_cos = rot.y.cos();
-    let z_sin = rot.z.sin();
-    let z_cos = rot.z.cos();
+// Rotates by `rotation` first, then moves to `pos`
+pub fn transform(pos: &Vector, rotation: &Quaternion) -> Matrix4<f32> {
+    let translation = mat4(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        pos.x, pos.y, pos.z, 1.0,
+    );
+    translation * rotation.to_matrix()
+}
 
-    mat4(
-        z_cos * y_cos, (z_cos * y_sin * x_sin) - (z_sin * x_cos), (z_cos * y_sin * x_cos) + (z_sin * x_sin), 0.0,
-        z_sin * y_cos, (z_sin * y_sin * x_sin) + (z_cos * x_cos), (z_sin * y_sin * x_cos) - (z_cos * x_sin), 0.0,
-        -y_sin, y_cos * x_sin, y_cos * x_cos,                                                                0.0,
-        pos.x, pos.y, pos.z, 1.0
-    )
+// `transform` with the rotation as radians around x, then y, then z
+pub fn trasform(pos: &Vector, rot: &Vector) -> Matrix4<f32> {
+    transform(pos, &Quaternion::from_euler(rot))
 }
 
+// Counter-clockwise seen from where the axis points, mat4 takes columns
 pub fn xrotation(rads: f32) -> Matrix4<f32> {
     let sincos = (rads.sin(), rads.cos());
     mat4(
         1.0,    0.0,       0.0,       0.0,
-        0.0,    sincos.1,  -sincos.0, 0.0,
-        0.0,    sincos.0,  sincos.1,  0.0,
+        0.0,    sincos.1,  sincos.0,  0.0,
+        0.0,    -sincos.0, sincos.1,  0.0,
         0.0,    0.0,       0.0,       1.0,
     )
 }
@@ -40,9 +43,9 @@ pub fn xrotation(rads: f32) -> Matrix4<f32> {
 pub fn yrotation(rads: f32) -> Matrix4<f32> {
     let sincos = (rads.sin(), rads.cos());
     mat4(
-        sincos.1,    0.0,   sincos.0,  0.0,
+        sincos.1,    0.0,   -sincos.0, 0.0,
         0.0,         1.0,   0.0,       0.0,
-        -sincos.0,   0.0,   sincos.1,  0.0,
+        sincos.0,    0.0,   sincos.1,  0.0,
         0.0,         0.0,   0.0,       1.0,
     )
 }
@@ -50,8 +53,8 @@ pub fn yrotation(rads: f32) -> Matrix4<f32> {
 pub fn zrotation(rads: f32) -> Matrix4<f32> {
     let sincos = (rads.sin(), rads.cos());
     mat4(
-        sincos.1,   -sincos.0,  0.0,   0.0,
-        sincos.0,    sincos.1,  0.0,   0.0,
+        sincos.1,    sincos.0,  0.0,   0.0,
+        -sincos.0,   sincos.1,  0.0,   0.0,
         0.0,         0.0,       1.0,   0.0,
         0.0,         0.0,       0.0,   1.0,
     )
@@ -64,4 +67,47 @@ pub fn matrix4_to_array(mat: Matrix4<f32>) -> [f32; 16] {
         vals.push(v.as_array().clone());
     }
     vals.concat()[..].try_into().expect("slice with incorrect length")
-}
\ No newline at end of file
+}
+
+// Radical inverse of `index` in the given base, used for low-discrepancy jitter
+pub fn halton(index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut f = 1.0;
+    let mut i = index;
+    while i > 0 {
+        f /= base as f32;
+        result += f * (i % base) as f32;
+        i /= base;
+    }
+    result
+}
+
+pub fn array_to_matrix4(vals: &[f32; 16]) -> Matrix4<f32> {
+    mat4(
+        vals[0], vals[1], vals[2], vals[3],
+        vals[4], vals[5], vals[6], vals[7],
+        vals[8], vals[9], vals[10], vals[11],
+        vals[12], vals[13], vals[14], vals[15],
+    )
+}
+
+// Local time isn't available without extra dependencies, so this formats UTC as YYYYMMDD-HHMMSS
+pub fn timestamp() -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0) as i64;
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // civil date from days since 1970-01-01
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, rem / 3600, (rem % 3600) / 60, rem % 60)
+}
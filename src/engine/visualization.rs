@@ -0,0 +1,173 @@
+use super::stats::Tracked;
+use super::targets::{VELOCITY_FORMAT, DEPTH_FORMAT, DepthMode};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VisualizationMode {
+    Shaded,
+    Wireframe,
+    Normals,
+    Uvs,
+    Depth,
+    Overdraw,
+    MeshColor,
+    LightComplexity,
+}
+
+pub const MODES: [VisualizationMode; 8] = [
+    VisualizationMode::Shaded,
+    VisualizationMode::Wireframe,
+    VisualizationMode::Normals,
+    VisualizationMode::Uvs,
+    VisualizationMode::Depth,
+    VisualizationMode::Overdraw,
+    VisualizationMode::MeshColor,
+    VisualizationMode::LightComplexity,
+];
+
+impl VisualizationMode {
+    pub fn next(self) -> VisualizationMode {
+        let index = MODES.iter().position(|mode| *mode == self).unwrap();
+        MODES[(index + 1) % MODES.len()]
+    }
+
+    // Fragment shader permutation compiled by build.rs for this mode
+    fn fragment_spirv(self) -> &'static [u8] {
+        match self {
+            VisualizationMode::Shaded => include_bytes!("../../compiled_shaders/shader.frag.spv"),
+            VisualizationMode::Wireframe => include_bytes!("../../compiled_shaders/shader.frag.VIS_WIREFRAME.spv"),
+            VisualizationMode::Normals => include_bytes!("../../compiled_shaders/shader.frag.VIS_NORMALS.spv"),
+            VisualizationMode::Uvs => include_bytes!("../../compiled_shaders/shader.frag.VIS_UVS.spv"),
+            VisualizationMode::Depth => include_bytes!("../../compiled_shaders/shader.frag.VIS_DEPTH.spv"),
+            VisualizationMode::Overdraw => include_bytes!("../../compiled_shaders/shader.frag.VIS_OVERDRAW.spv"),
+            VisualizationMode::MeshColor => include_bytes!("../../compiled_shaders/shader.frag.VIS_MESH_COLOR.spv"),
+            VisualizationMode::LightComplexity => include_bytes!("../../compiled_shaders/shader.frag.VIS_LIGHT_COMPLEXITY.spv"),
+        }
+    }
+}
+
+// One scene pipeline per visualization mode. Wireframe is an overlay: the shaded
+// pipeline draws first and the wireframe pipeline draws the triangle edges on top.
+pub struct ScenePipelines {
+    pipelines: Vec<Tracked<wgpu::RenderPipeline>>,
+}
+
+impl ScenePipelines {
+    pub fn new(device: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout, vertex_buffer: wgpu::VertexBufferDescriptor, format: wgpu::TextureFormat, samples: u32, depth_mode: DepthMode) -> ScenePipelines {
+        let vs = include_bytes!("../../compiled_shaders/shader.vert.spv");
+        let vs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[bind_group_layout],
+        });
+
+        let pipelines = MODES.iter().map(|mode| {
+            let fs = mode.fragment_spirv();
+            let fs_module = device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(fs)).unwrap());
+            Tracked::new(create_pipeline(device, &pipeline_layout, &vs_module, &fs_module, vertex_buffer.clone(), format, samples, *mode, depth_mode))
+        }).collect();
+
+        ScenePipelines {
+            pipelines: pipelines,
+        }
+    }
+
+    pub fn get(&self, mode: VisualizationMode) -> &wgpu::RenderPipeline {
+        let index = MODES.iter().position(|m| *m == mode).unwrap();
+        &self.pipelines[index]
+    }
+}
+
+// Turns a triangle list into the list of its edges, shared edges are drawn twice
+pub fn edge_indices(indices: &[u16]) -> Vec<u16> {
+    let mut edges = Vec::with_capacity(indices.len() * 2);
+    for triangle in indices.chunks(3) {
+        if let [a, b, c] = triangle {
+            edges.extend_from_slice(&[*a, *b, *b, *c, *c, *a]);
+        }
+    }
+    edges
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    vs_module: &wgpu::ShaderModule,
+    fs_module: &wgpu::ShaderModule,
+    vertex_buffer: wgpu::VertexBufferDescriptor,
+    format: wgpu::TextureFormat,
+    samples: u32,
+    mode: VisualizationMode,
+    depth_mode: DepthMode,
+) -> wgpu::RenderPipeline {
+    let (topology, cull_mode) = match mode {
+        VisualizationMode::Wireframe => (wgpu::PrimitiveTopology::LineList, wgpu::CullMode::None),
+        // count hidden surfaces too
+        VisualizationMode::Overdraw => (wgpu::PrimitiveTopology::TriangleList, wgpu::CullMode::None),
+        _ => (wgpu::PrimitiveTopology::TriangleList, wgpu::CullMode::Back),
+    };
+
+    let color_blend = match mode {
+        VisualizationMode::Overdraw => wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        },
+        _ => wgpu::BlendDescriptor::REPLACE,
+    };
+
+    let (depth_write_enabled, depth_compare) = match mode {
+        VisualizationMode::Overdraw => (false, wgpu::CompareFunction::Always),
+        VisualizationMode::Wireframe => (false, wgpu::CompareFunction::LessEqual),
+        _ => (true, wgpu::CompareFunction::Less),
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        layout: layout,
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: vs_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: fs_module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: cull_mode,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: topology,
+        color_states: &[
+            wgpu::ColorStateDescriptor {
+                format: format,
+                color_blend: color_blend,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            },
+            wgpu::ColorStateDescriptor {
+                format: VELOCITY_FORMAT,
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            },
+        ],
+        depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: depth_write_enabled,
+            depth_compare: depth_mode.compare(depth_compare),
+            stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_read_mask: 0,
+            stencil_write_mask: 0,
+        }),
+        vertex_state: wgpu::VertexStateDescriptor {
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffers: &[vertex_buffer],
+        },
+        sample_count: samples,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
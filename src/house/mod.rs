@@ -1,28 +1,78 @@
-use std::time::{Instant};
+use std::env;
+use std::process;
 use winit::{
     event::{Event, WindowEvent},
     event_loop::{ControlFlow},
 };
 
-use super::engine::{Engine};
+use super::engine::{Engine, Vector};
+use super::engine::config::ConfigSource;
+use super::engine::timestep::{FixedTimestep, FIXED_DT};
+use super::engine::debug_draw::YELLOW;
+
+const HUD_TEXT_SIZE: f32 = 18.0;
+const HUD_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
 
 pub fn main(title: &str) {
-    let (window, event_loop) = Engine::get_init(&title);
-    let mut engine = Engine::new(&window);
+    let source = ConfigSource::from_args(env::args().skip(1)).unwrap_or_else(|err| {
+        println!("{}", err);
+        process::exit(2);
+    });
+    let config = source.load().unwrap_or_else(|err| {
+        println!("Failed to load the config: {}", err);
+        process::exit(1);
+    });
 
-    let mut new_time = Instant::now();
-    let mut old_time = Instant::now();
+    let (window, event_loop) = Engine::get_init(&title, &config.window);
+    let mut engine = Engine::new(&window, config);
+    // a replay from the command line checks the camera follows it and exits with the result
+    let replay = source.replay.clone();
+    if let Some(path) = &replay {
+        if let Err(err) = engine.replay_inputs(&window, path) {
+            println!("Failed to replay {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+    engine.watch_config(source);
+
+    let mut timestep = FixedTimestep::new(FIXED_DT);
+    let mut idle = false;
 
     event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Poll;
+        engine.handle_ui_event(&window, &event);
+        engine.handle_event(&window, &event);
 
-        old_time = new_time;
-        new_time = Instant::now();
+        match event {
+            Event::MainEventsCleared => {
+                engine.poll_config(&window);
+                if idle {
+                    timestep.resume();
+                }
+                // a recording steps the simulation by whole frames, however long they take to render
+                let ticks = match engine.recording_frame_dt() {
+                    Some(frame_dt) => timestep.advance_by(frame_dt),
+                    None => timestep.advance(),
+                };
+                for _ in 0..ticks {
+                    engine.update(&window, timestep.dt);
+                    draw_debug(&mut engine);
+                }
+                if replay.is_some() {
+                    if let Some(outcome) = engine.take_replay_outcome() {
+                        process::exit(if outcome.diverged_at.is_some() { 1 } else { 0 });
+                    }
+                }
 
-        engine.update(&event, new_time.duration_since(old_time).as_secs_f32());
+                let (draw, flow) = engine.schedule_frame();
+                if draw {
+                    window.request_redraw();
+                }
 
-        match event {
-            Event::MainEventsCleared => window.request_redraw(),
+                idle = engine.is_idle();
+                if *control_flow != ControlFlow::Exit {
+                    *control_flow = flow;
+                }
+            },
 
             // Window Resized
             Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
@@ -37,9 +87,28 @@ pub fn main(title: &str) {
 
             // Draw
             Event::RedrawRequested(_) => {
-                engine.render(event);         
+                draw_hud(&mut engine);
+                engine.render(&window, timestep.alpha());
             },
             _ => (),
         }
     });
-}
\ No newline at end of file
+}
+
+fn draw_debug(engine: &mut Engine) {
+    let debug = engine.debug_draw();
+    debug.axes(&Vector::new(0.0, 0.0, 0.0), 2.0, None);
+    debug.aabb(&Vector::new(-1.0, -1.0, -1.0), &Vector::new(1.0, 1.0, 1.0), YELLOW, None);
+}
+
+fn draw_hud(engine: &mut Engine) {
+    let position = engine.camera().position;
+    // averaged so the numbers are readable
+    let frame_time = engine.stats().average_frame_ms() / 1000.0;
+    let fps = if frame_time > 0.0 { 1.0 / frame_time } else { 0.0 };
+
+    let text = engine.text();
+    text.draw(&format!("{:.0} fps ({:.2} ms)", fps, frame_time * 1000.0), 10.0, 10.0, HUD_TEXT_SIZE, HUD_COLOR);
+    text.draw(&format!("camera {:.2} {:.2} {:.2}", position.x, position.y, position.z), 10.0, 32.0, HUD_TEXT_SIZE, HUD_COLOR);
+    text.draw_world("Cube", &Vector::new(0.0, 1.5, 0.0), HUD_TEXT_SIZE, HUD_COLOR);
+}